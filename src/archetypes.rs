@@ -0,0 +1,223 @@
+use hyperfold_engine::utils::number::Number;
+
+use crate::{
+    param_dag::{Node, NodeTrait, NumDag, Root},
+    stats::{Stat, StatAdd, StatMult},
+    upgrade_defs::{Prereq, UpgradeGroups},
+    upgrades::{Cost, UpgradeOwner},
+    wizard::{Wizard, WizardNumbers},
+};
+
+// Every kind of wizard, the player controls the first Fire wizard
+#[derive(Copy, Clone, PartialEq, Eq)]
+#[repr(u8)]
+pub enum WizardType {
+    Fire,
+    Power,
+    Catalyst,
+}
+
+// How a wizard turns its power into magic each time its timer fires
+#[derive(Copy, Clone)]
+pub enum Attack {
+    // One fireball at the crystal
+    Fireball,
+    // Fireballs from points spread around the wizard
+    Volley(u32),
+    // Magic goes straight into the crystal, no projectile
+    Channel,
+}
+
+impl Attack {
+    // Hits per attack, each worth the wizard's power
+    pub fn hits(&self) -> u32 {
+        match self {
+            Attack::Volley(n) => *n,
+            Attack::Fireball | Attack::Channel => 1,
+        }
+    }
+}
+
+pub struct Archetype {
+    // Used in saves, must not change
+    pub key: &'static str,
+    pub name: &'static str,
+    // Upgrade box shown when the wizard is clicked, see UpgradeGroups
+    pub group: &'static str,
+    pub sprite: &'static str,
+    // Frame count and ms per frame of the sprite sheet
+    pub frames: (u32, u32),
+    pub size: f32,
+    pub fire_rate_ms: u32,
+    // Multiplier on the shared wizard power
+    pub power: f64,
+    pub attack: Attack,
+    // Cost of the nth hire
+    pub hire_cost: Cost,
+    // Hiring is shown once met
+    pub reveal: Prereq,
+}
+
+static ARCHETYPES: [Archetype; 3] = [
+    Archetype {
+        key: "fire",
+        name: "Fire Wizard",
+        group: "wizard",
+        sprite: "res/wizards/wizard_ss.png",
+        frames: (5, 150),
+        size: 100.0,
+        fire_rate_ms: 1000,
+        power: 1.0,
+        attack: Attack::Fireball,
+        hire_cost: Cost::Geometric {
+            base: 1000.0,
+            ratio: 4.0,
+        },
+        reveal: Prereq::Magic(500.0),
+    },
+    Archetype {
+        key: "power",
+        name: "Power Wizard",
+        group: "power_wizard",
+        sprite: "res/wizards/power_wizard_ss.png",
+        frames: (8, 100),
+        size: 80.0,
+        fire_rate_ms: 3000,
+        power: 2.0,
+        attack: Attack::Volley(3),
+        hire_cost: Cost::Geometric {
+            base: 25000.0,
+            ratio: 6.0,
+        },
+        reveal: Prereq::Magic(10000.0),
+    },
+    Archetype {
+        key: "catalyst",
+        name: "Catalyst Wizard",
+        group: "catalyst_wizard",
+        sprite: "res/wizards/catalyst.png",
+        frames: (1, 1000),
+        size: 80.0,
+        fire_rate_ms: 500,
+        power: 0.5,
+        attack: Attack::Channel,
+        hire_cost: Cost::Geometric {
+            base: 1e6,
+            ratio: 10.0,
+        },
+        reveal: Prereq::Prestiges(1),
+    },
+];
+
+// Upgrade group ids for the types that don't have their own component
+pub struct PowerWizard;
+pub struct CatalystWizard;
+
+impl WizardType {
+    pub const ALL: [WizardType; 3] = [WizardType::Fire, WizardType::Power, WizardType::Catalyst];
+
+    pub fn archetype(&self) -> &'static Archetype {
+        &ARCHETYPES[*self as usize]
+    }
+
+    pub fn from_key(key: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|t| t.archetype().key == key)
+    }
+
+    // Upgrades that only affect this type
+    pub fn stat(&self) -> Stat {
+        match self {
+            WizardType::Fire => Stat::FireWizard,
+            WizardType::Power => Stat::PowerWizard,
+            WizardType::Catalyst => Stat::CatalystWizard,
+        }
+    }
+
+    pub fn owner(&self) -> UpgradeOwner {
+        match self {
+            WizardType::Fire => UpgradeOwner::new(Wizard),
+            WizardType::Power => UpgradeOwner::new(PowerWizard),
+            WizardType::Catalyst => UpgradeOwner::new(CatalystWizard),
+        }
+    }
+}
+
+// Value of each hit from one wizard of the type
+#[derive(Copy, Clone)]
+pub struct WizardPower(pub WizardType);
+
+impl NodeTrait for WizardPower {
+    fn idx(&self) -> u8 {
+        self.0 as u8
+    }
+}
+
+impl Node<Number> for WizardPower {}
+
+// Wizards of the type, including the player's
+#[derive(Copy, Clone)]
+pub struct WizardCount(pub WizardType);
+
+impl NodeTrait for WizardCount {
+    fn idx(&self) -> u8 {
+        self.0 as u8
+    }
+}
+
+impl Root<Number> for WizardCount {
+    fn default(&self) -> Number {
+        match self.0 {
+            WizardType::Fire => 1,
+            _ => 0,
+        }
+        .into()
+    }
+}
+
+#[hyperfold_engine::system(Init)]
+fn init_archetype_numbers(dag: &mut NumDag) {
+    for ty in WizardType::ALL {
+        let (stat, factor) = (ty.stat(), Number::from(ty.archetype().power));
+        dag.0.add_node(
+            WizardPower(ty),
+            (
+                [
+                    (WizardNumbers::Power.id(), None),
+                    (StatAdd(stat).id(), None),
+                    (StatMult(stat).id(), None),
+                ],
+                move |[p, a, m]| (*p * factor + *a) * *m,
+            ),
+        );
+    }
+
+    // Magic per second from every wizard
+    let (values, rates): (Vec<_>, Vec<_>) = (WizardType::ALL.into_iter())
+        .map(|ty| {
+            let arch = ty.archetype();
+            let rate = arch.attack.hits() as f64 * 1000.0 / arch.fire_rate_ms as f64;
+            (
+                [
+                    (WizardCount(ty).id(), Some(Root::default(&WizardCount(ty)))),
+                    (WizardPower(ty).id(), None),
+                ],
+                Number::from(rate),
+            )
+        })
+        .unzip();
+    // Fire rate first, then count and power for each type
+    let values = [vec![(WizardNumbers::FireRate.id(), None)], values.concat()].concat();
+    dag.0
+        .add_node_dyn(WizardNumbers::Income, values, move |values| {
+            let income = (values[1..].chunks(2).zip(&rates))
+                .fold(0.into(), |s, (v, r)| s + *v[0] * *v[1] * *r);
+            income * *values[0]
+        });
+}
+
+#[hyperfold_engine::system(Init)]
+fn init_archetype_groups(groups: &mut UpgradeGroups) {
+    groups.add(WizardType::Fire.archetype().group, Wizard);
+    groups.add(WizardType::Power.archetype().group, PowerWizard);
+    groups.add(WizardType::Catalyst.archetype().group, CatalystWizard);
+}
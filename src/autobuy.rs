@@ -0,0 +1,348 @@
+use std::collections::HashMap;
+
+use hyperfold_engine::{
+    add_components, components,
+    ecs::{
+        entities::{Entity, NewEntity},
+        events::core::Update,
+    },
+    framework::{
+        event_system::events::Click,
+        physics::Position,
+        render_system::{
+            font::{FontData, TIMES},
+            render_data::Fit,
+            render_text::RenderText,
+            RenderComponent, RenderOpts,
+        },
+    },
+    utils::{
+        colors::{GRAY, WHITE},
+        number::Number,
+        rect::{Align, Rect},
+        timer::{Timer, TimerTrait},
+        util::AsType,
+    },
+};
+
+use crate::{
+    _engine::{Components, Events},
+    crystal::CrystalNumbers,
+    input::{Action, ActionPressed},
+    param_dag::NumDag,
+    research::ResearchQueue,
+    unlocks::Unlocks,
+    upgrade_defs::{Prereq, UpgradeGroups},
+    upgrades::{BuyMode, BuyUpgrade, UpgradeBoxPos, UpgradeState, Upgrades},
+    utils::elevations::Elevations,
+};
+
+// Autobuyers for every group unlock after the first catalyze
+const UNLOCK: Prereq = Prereq::Prestiges(1);
+// Reserve options cycled through by the reserve button
+const RESERVES: [f64; 5] = [0.0, 0.1, 0.25, 0.5, 0.9];
+
+fn unlock_key(group: &str) -> String {
+    format!("autobuy.{group}")
+}
+
+// Per group settings, persisted with the save
+#[derive(Clone)]
+pub struct AutobuySettings {
+    pub enabled: bool,
+    // Upgrade keys bought first, the rest follow in group order
+    pub priority: Vec<String>,
+    // Fraction of magic that is never spent
+    pub reserve: f64,
+    pub interval_ms: u32,
+}
+
+impl AutobuySettings {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            priority: Vec::new(),
+            reserve: 0.0,
+            interval_ms: 1000,
+        }
+    }
+
+    // enabled;reserve;interval_ms;priority,...
+    pub fn encode(&self) -> String {
+        format!(
+            "{};{};{};{}",
+            self.enabled as u8,
+            self.reserve,
+            self.interval_ms,
+            self.priority.join(",")
+        )
+    }
+
+    pub fn decode(s: &str) -> Result<Self, String> {
+        let parts = s.split(';').collect::<Vec<_>>();
+        let [enabled, reserve, interval, priority] = parts[..] else {
+            return Err(format!(
+                "Expected 4 ';' separated values, found {}",
+                parts.len()
+            ));
+        };
+        Ok(Self {
+            enabled: enabled == "1",
+            priority: (priority.split(','))
+                .filter(|s| !s.is_empty())
+                .map(|s| s.to_string())
+                .collect(),
+            reserve: (reserve.parse::<f64>())
+                .map_err(|_| format!("Invalid reserve '{reserve}'"))?
+                .clamp(0.0, 1.0),
+            interval_ms: (interval.parse::<u32>())
+                .map_err(|_| format!("Invalid interval '{interval}'"))?
+                .max(1),
+        })
+    }
+
+    // Moves the upgrade to the front of the priority list
+    pub fn prioritize(&mut self, key: &str) {
+        self.priority.retain(|k| k != key);
+        self.priority.insert(0, key.to_string());
+    }
+
+    fn rank(&self, key: &str) -> usize {
+        (self.priority.iter())
+            .position(|k| k == key)
+            .unwrap_or(self.priority.len())
+    }
+}
+
+pub struct Autobuyer {
+    pub settings: AutobuySettings,
+    timer: Timer,
+}
+
+impl Autobuyer {
+    pub fn new(settings: AutobuySettings) -> Self {
+        Self {
+            timer: Timer::new(settings.interval_ms),
+            settings,
+        }
+    }
+}
+
+// Autobuyers keyed by upgrade group name, see UpgradeGroups
+#[hyperfold_engine::global]
+struct Autobuyers(HashMap<String, Autobuyer>);
+
+impl Autobuyers {
+    pub fn new() -> Self {
+        Self(HashMap::new())
+    }
+
+    pub fn get_mut(&mut self, group: &str) -> &mut Autobuyer {
+        self.0
+            .entry(group.to_string())
+            .or_insert_with(|| Autobuyer::new(AutobuySettings::new()))
+    }
+
+    pub fn set(&mut self, group: &str, settings: AutobuySettings) {
+        self.0.insert(group.to_string(), Autobuyer::new(settings));
+    }
+
+    pub fn settings(&self) -> Vec<(String, AutobuySettings)> {
+        (self.0.iter())
+            .map(|(group, a)| (group.clone(), a.settings.clone()))
+            .collect()
+    }
+}
+
+// Buys at most one level per tick, through the same event as clicking
+#[hyperfold_engine::system]
+fn autobuy(
+    Update(dt): &Update,
+    upgrades: Vec<Upgrades>,
+    autobuyers: &mut Autobuyers,
+    groups: &UpgradeGroups,
+    unlocks: &mut Unlocks,
+    research: &ResearchQueue,
+    events: &mut dyn Events,
+    dag: &mut NumDag,
+) {
+    for (group, id) in &groups.0 {
+        if !unlocks.is_unlocked(&unlock_key(group)) {
+            match UNLOCK.met(&mut dag.0) {
+                true => unlocks.unlock(&unlock_key(group)),
+                false => continue,
+            };
+        }
+
+        let Autobuyer { settings, timer } = autobuyers.get_mut(group);
+        if timer.add_time(*dt) == 0 || !settings.enabled {
+            continue;
+        }
+
+        let magic = *dag.0.get(CrystalNumbers::Magic);
+        let budget = magic * Number::from(1.0 - settings.reserve);
+        let mut candidates = (upgrades.iter())
+            .filter(|u| u.up.group() == *id && u.up.state() == UpgradeState::Available)
+            .filter(|u| !research.contains(&u.up.key))
+            .collect::<Vec<_>>();
+        candidates.sort_by_key(|u| (settings.rank(&u.up.key), u.up.idx()));
+        if let Some(u) = candidates.into_iter().find(|u| {
+            u.up.purchase(&mut dag.0, BuyMode::One)
+                .is_some_and(|(_, cost)| cost <= budget)
+        }) {
+            events.new_event(BuyUpgrade {
+                eid: *u.eid,
+                mode: BuyMode::One,
+            });
+        }
+    }
+}
+
+// Moves the selected upgrade to the front of its autobuyer's priority list
+#[hyperfold_engine::system]
+fn prioritize_upgrade(
+    ActionPressed(action): &ActionPressed,
+    upgrades: Vec<Upgrades>,
+    UpgradeBoxPos { up_box, .. }: UpgradeBoxPos,
+    autobuyers: &mut Autobuyers,
+    groups: &UpgradeGroups,
+) {
+    if *action != Action::Prioritize {
+        return;
+    }
+
+    if let Some((group, up)) = up_box
+        .selected()
+        .and_then(|e| upgrades.into_iter().find(|u| u.eid == &e))
+        .and_then(|u| groups.name(u.up.group()).map(|g| (g, u.up)))
+    {
+        autobuyers.get_mut(group).settings.prioritize(&up.key);
+    }
+}
+
+// Controls shown next to the buy mode button while the open group's autobuyer is unlocked
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum AutobuyControl {
+    Toggle,
+    Reserve,
+}
+
+#[hyperfold_engine::component]
+struct AutobuyButton {
+    control: AutobuyControl,
+    text: String,
+}
+
+#[hyperfold_engine::system(Init)]
+fn init_autobuy(entities: &mut dyn Components) {
+    for control in [AutobuyControl::Toggle, AutobuyControl::Reserve] {
+        let e = Entity::new();
+        add_components!(
+            entities,
+            e,
+            AutobuyButton {
+                control,
+                text: String::new()
+            },
+            Position(Rect::new()),
+            RenderOpts::new(Elevations::UpgradeBadges as u8)
+                .absolute()
+                .is_visible(false),
+            RenderComponent::new(
+                RenderText::new(FontData {
+                    w: None,
+                    h: Some(20),
+                    sample: "Keep 25%".to_string(),
+                    file: TIMES.to_string()
+                })
+                .with_text_color(WHITE)
+                .with_background_color(GRAY)
+                .with_dest_fit(Fit::fit_height())
+                .with_dest_align(Align::TopLeft, Align::TopLeft)
+            )
+        );
+    }
+}
+
+components!(
+    AutobuyButtons,
+    button: &'a mut AutobuyButton,
+    pos: &'a mut Position,
+    opts: &'a mut RenderOpts,
+    text: &'a mut RenderComponent
+);
+
+fn button_text(control: AutobuyControl, settings: &AutobuySettings) -> String {
+    match (control, settings.enabled) {
+        (AutobuyControl::Toggle, true) => "Auto: On".to_string(),
+        (AutobuyControl::Toggle, false) => "Auto: Off".to_string(),
+        (AutobuyControl::Reserve, _) => format!("Keep {}%", (settings.reserve * 100.0).round()),
+    }
+}
+
+#[hyperfold_engine::system]
+fn click_autobuy(
+    m: &Click,
+    buttons: Vec<AutobuyButtons>,
+    UpgradeBoxPos { up_box, .. }: UpgradeBoxPos,
+    autobuyers: &mut Autobuyers,
+    groups: &UpgradeGroups,
+) {
+    let (button, group) = match (
+        buttons.into_iter().find(|b| m.is_me(b.eid)),
+        up_box.open_id().and_then(|id| groups.name(id)),
+    ) {
+        (Some(button), Some(group)) => (button, group),
+        _ => return,
+    };
+
+    let settings = &mut autobuyers.get_mut(group).settings;
+    match button.button.control {
+        AutobuyControl::Toggle => settings.enabled = !settings.enabled,
+        AutobuyControl::Reserve => {
+            settings.reserve = (RESERVES.iter())
+                .find(|r| **r > settings.reserve)
+                .copied()
+                .unwrap_or(RESERVES[0])
+        }
+    }
+}
+
+#[hyperfold_engine::system]
+fn update_autobuy_buttons(
+    _: &Update,
+    buttons: Vec<AutobuyButtons>,
+    UpgradeBoxPos { up_box, pos, .. }: UpgradeBoxPos,
+    autobuyers: &mut Autobuyers,
+    groups: &UpgradeGroups,
+    unlocks: &Unlocks,
+) {
+    let group = up_box
+        .open_id()
+        .and_then(|id| groups.name(id))
+        .filter(|g| unlocks.is_unlocked(&unlock_key(g)));
+
+    // Buy mode button takes the first slot
+    const W: f32 = 90.0;
+    for b in buttons {
+        b.opts.set_visible(group.is_some());
+        let settings = match group {
+            Some(group) => &autobuyers.get_mut(group).settings,
+            None => continue,
+        };
+
+        b.pos.0 = Rect::from(
+            pos.0.x + 70.0 + W * b.button.control as u8 as f32,
+            pos.0.y + pos.0.h,
+            W - 10.0,
+            25.0,
+            Align::TopLeft,
+            Align::TopLeft,
+        );
+        let text = button_text(b.button.control, settings);
+        if text != b.button.text {
+            b.text.try_as_mut(|t: &mut RenderText| t.set_text(&text));
+            b.button.text = text;
+        }
+    }
+}
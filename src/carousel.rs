@@ -0,0 +1,389 @@
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+
+use hyperfold_engine::{
+    components,
+    ecs::{entities::Entity, events::core::Update},
+    f32,
+    framework::{
+        event_system::{
+            events::Click,
+            mouse::{Drag, DragStart, DragState},
+        },
+        physics::Position,
+        render_system::{
+            drawable::Canvas,
+            shapes::{Rectangle, ShapeTrait},
+            Renderer, Texture,
+        },
+    },
+    sdl2::SDL_Color,
+    utils::{
+        math::NormalizeAngle,
+        rect::{Align, PointF, Rect},
+    },
+};
+use itertools::Itertools;
+
+// Angle of the front of the ellipse, where the first item sits at 0 scroll
+const FRONT_ANGLE: f32 = 3.0 * FRAC_PI_2;
+// Momentum below this speed settles on the nearest item
+const SNAP_SPEED: f32 = 200.0;
+const SNAP_MS: f32 = 250.0;
+
+#[derive(Copy, Clone)]
+pub struct CarouselShape {
+    // Size of the front item as a fraction of the carousel height
+    // Also the scroll distance between each item
+    pub item_size: f32,
+    // Raises the ellipse center by this fraction of the item size
+    pub lift: f32,
+    // Fraction of the free space used by the x/y radii of the ellipse
+    pub radii: (f32, f32),
+    // Items at the sides are this many times closer together than at the front
+    pub packing: f32,
+    // Item scale from 0 at the back to 1 at the front
+    pub depth: fn(f32) -> f32,
+}
+
+impl Default for CarouselShape {
+    fn default() -> Self {
+        Self {
+            item_size: 0.5,
+            lift: 0.25,
+            radii: (1.0, 1.0),
+            packing: 4.0,
+            depth: |t| t,
+        }
+    }
+}
+
+// An item laid out on the ellipse, relative to the carousel
+pub struct CarouselItem {
+    pub rect: Rect,
+    pub idx: usize,
+    pub angle: f32,
+    // Scale from CarouselShape::depth, 1 at the front
+    pub depth: f32,
+    // Draw order, 0 is drawn first at the back
+    pub layer: usize,
+}
+
+// Eased scroll towards an item
+#[derive(Copy, Clone)]
+struct Snap {
+    from: f32,
+    to: f32,
+    // Progress from 0 to 1
+    t: f32,
+}
+
+fn ease_out_cubic(t: f32) -> f32 {
+    1.0 - (1.0 - t).powi(3)
+}
+
+// Items placed around an ellipse, scrolled by dragging
+// The carousel lays out and draws the item slots, the owner fills them through render()
+#[hyperfold_engine::component]
+struct Carousel {
+    pub shape: CarouselShape,
+    len: usize,
+    scroll: f32,
+    v_scroll: f32,
+    max_scroll: f32,
+    // Scroll that brings each item to the front
+    stops: Vec<f32>,
+    snap: Option<Snap>,
+    // Sorted back to front
+    items: Vec<CarouselItem>,
+    // Set when the scroll changes
+    pub update: bool,
+    // Distance dragged since the mouse was pressed, used to tell clicks from drags
+    drag_dist: f32,
+}
+
+impl Carousel {
+    pub fn new(shape: CarouselShape) -> Self {
+        Self {
+            shape,
+            len: 0,
+            scroll: 0.0,
+            v_scroll: 0.0,
+            max_scroll: 0.0,
+            stops: Vec::new(),
+            snap: None,
+            items: Vec::new(),
+            update: true,
+            drag_dist: 0.0,
+        }
+    }
+
+    pub fn set_len(&mut self, len: usize) {
+        if len != self.len {
+            self.len = len;
+            self.update = true;
+        }
+    }
+
+    pub fn scroll(&self) -> f32 {
+        self.scroll
+    }
+
+    pub fn set_scroll(&mut self, scroll: f32) {
+        self.scroll = scroll;
+        self.v_scroll = 0.0;
+        self.snap = None;
+        self.update = true;
+    }
+
+    // The item closest to the front, once laid out
+    pub fn front(&self) -> Option<usize> {
+        self.nearest(self.scroll)
+    }
+
+    fn nearest(&self, scroll: f32) -> Option<usize> {
+        (self.stops.iter().enumerate())
+            .min_by(|(_, a), (_, b)| (*a - scroll).abs().total_cmp(&(*b - scroll).abs()))
+            .map(|(i, _)| i)
+    }
+
+    // Eases the item to the front
+    pub fn scroll_to(&mut self, idx: usize) {
+        if let Some(to) = self.stops.get(idx) {
+            self.v_scroll = 0.0;
+            self.snap = Some(Snap {
+                from: self.scroll,
+                to: *to,
+                t: 0.0,
+            });
+        }
+    }
+
+    // Moves the front by the given number of items
+    pub fn step(&mut self, di: i32) {
+        // Step from where the current animation ends so repeated steps add up
+        if let Some(idx) = self.nearest(self.snap.map_or(self.scroll, |s| s.to)) {
+            let idx = (idx as i32 + di).clamp(0, self.stops.len() as i32 - 1);
+            self.scroll_to(idx as usize);
+        }
+    }
+
+    // Laid out items, back to front
+    pub fn items(&self) -> &Vec<CarouselItem> {
+        &self.items
+    }
+
+    // Draws each item's slot back to front onto a texture the size of bounds
+    // draw_item gets the item, its rect in screen coordinates and its depth, and returns the slot color
+    pub fn render(
+        &self,
+        r: &Renderer,
+        bounds: &Rect,
+        background: SDL_Color,
+        mut draw_item: impl FnMut(&CarouselItem, Rect, f32) -> SDL_Color,
+    ) -> Texture {
+        let tex = Texture::new(r, bounds.w_i32() as u32, bounds.h_i32() as u32, background);
+        for item in &self.items {
+            let mut rect = item.rect;
+            rect.x += bounds.x;
+            rect.y += bounds.y;
+            let color = draw_item(item, rect, item.depth);
+            tex.draw(r, &mut Rectangle::new().fill(item.rect).set_color(color));
+        }
+        tex
+    }
+
+    // Returns true if the click is on this carousel and didn't end a drag further than slop
+    // Only clicks on the carousel reset the drag distance
+    pub fn is_click(&mut self, m: &Click, eid: &Entity, slop: f32) -> bool {
+        m.is_me(eid) && std::mem::replace(&mut self.drag_dist, 0.0) <= slop
+    }
+
+    // Recomputes item rects for the given bounds
+    pub fn layout(&mut self, bounds: &Rect) {
+        let CarouselShape {
+            item_size,
+            lift,
+            radii,
+            packing: m,
+            depth,
+        } = self.shape;
+
+        let w = bounds.h() * item_size;
+        let (cx, cy) = (bounds.half_w(), bounds.half_h() - w * lift);
+        // x/y radii of the ellipse
+        let (rx, ry) = (
+            (bounds.w() - w) / 2.0 * radii.0,
+            (bounds.h() - w) / 2.0 * radii.1,
+        );
+
+        let get_rect = |angle: f32| {
+            let angle_diff = ((angle + 3.0 * FRAC_PI_2) % TAU).min((5.0 * FRAC_PI_2 - angle) % TAU);
+            let d = depth(angle_diff / PI);
+            let size = w * d;
+            let rect = Rect::from(
+                cx + rx * angle.cos(),
+                cy - ry * angle.sin(),
+                size,
+                size,
+                Align::Center,
+                Align::Center,
+            );
+            (rect, d)
+        };
+
+        // Conversion from scroll distance to angle displacement
+        let scroll_to_deg = PI / bounds.w();
+        // One width of scrolling separates each item
+        let step = w * scroll_to_deg;
+        // Decays from step @ a=0 to step/M @ a = PI/2
+        let lin_da = |a: f32| step * (1.0 - a / FRAC_PI_2 * (m - 1.0) / m);
+        // x = a + da, da = lin_da(PI - x)
+        // da(a) = (step*M*pi/2 + s*(M-1)*(a-pi)) / (M*pi/2 - s*(M-1))
+        let pred_da = |a: f32| {
+            let t = step * (m - 1.0);
+            let u = m * FRAC_PI_2;
+            (step * u + t * (a - PI)) / (u - t)
+        };
+        // Solve for da when a = PI/2
+        let min_step = pred_da(FRAC_PI_2);
+        // Full function for calculating the da step CCW from the current angle
+        let da = |a: f32| {
+            match a.normalize_rad() {
+                // Linear scaling
+                a if a <= FRAC_PI_2 - min_step => lin_da(a),
+                // Constant min step to smooth accross PI/2
+                a if a <= FRAC_PI_2 => min_step,
+                // Predictive linear scaling
+                a if a <= PI - step => pred_da(a),
+                // Constant max step
+                _ => step,
+            }
+        };
+
+        // Compute angular displacement of the first item
+        let mut angle = FRONT_ANGLE - self.scroll * scroll_to_deg;
+        // We want to trace out the total angle from the last item CW to the first item
+        // However da(a) is setup to calculate angles moving CCW
+        // Instead, we flip the last item accross the y-axis and trace out CCW
+        let mut max_angle = PI - FRONT_ANGLE;
+        self.stops.clear();
+        let mut items = Vec::new();
+        for idx in 0..self.len {
+            // Add a new rect to be rendered
+            if angle < 5.0 * FRAC_PI_2 {
+                if angle >= FRAC_PI_2 {
+                    let (rect, depth) = get_rect(angle);
+                    items.push(CarouselItem {
+                        rect,
+                        idx,
+                        angle,
+                        depth,
+                        layer: 0,
+                    });
+                }
+                angle += da(angle);
+            }
+            // Compute angle cost of all items
+            // This is also the scroll that brings each item to the front
+            self.stops
+                .push((max_angle - PI + FRONT_ANGLE) / scroll_to_deg);
+            if idx + 1 < self.len {
+                max_angle += da(max_angle);
+            }
+        }
+
+        self.max_scroll = (max_angle - PI + FRONT_ANGLE) / scroll_to_deg;
+        self.items = items
+            .into_iter()
+            // Sort top to bottom (render order)
+            .sorted_by(|a, b| {
+                a.angle
+                    .sin()
+                    .partial_cmp(&b.angle.sin())
+                    .expect("NaN When sorting carousel items")
+                    .reverse()
+            })
+            .enumerate()
+            .map(|(layer, item)| CarouselItem { layer, ..item })
+            .collect();
+    }
+
+    // Returns the index of the frontmost item under the point (in screen coordinates)
+    pub fn hit_test(&self, bounds: &Rect, p: PointF) -> Option<usize> {
+        let (x, y) = (p.x - bounds.x, p.y - bounds.y);
+        self.items
+            .iter()
+            .rev()
+            .find(|i| {
+                let r = &i.rect;
+                x >= r.x && x < r.x + r.w && y >= r.y && y < r.y + r.h
+            })
+            .map(|i| i.idx)
+    }
+
+    // Scrolls by the given distance, returns true if the items moved noticeably
+    fn scroll_by(&mut self, ds: f32) -> bool {
+        let prev = self.scroll;
+        self.scroll = (self.scroll + ds).max(0.0).min(self.max_scroll);
+        let moved = (prev - self.scroll).abs() >= 1.0;
+        self.update |= moved;
+        moved
+    }
+}
+
+components!(CarouselData, carousel: &'a mut Carousel);
+components!(CarouselPos, carousel: &'a Carousel, pos: &'a Position);
+
+// Dragging
+#[hyperfold_engine::system]
+fn drag_start_carousel(DragStart(id): &DragStart, carousels: Vec<CarouselData>) {
+    if let Some(CarouselData { carousel, .. }) = carousels.into_iter().find(|c| c.eid == id) {
+        carousel.v_scroll = 0.0;
+        carousel.snap = None;
+        carousel.drag_dist = 0.0;
+    }
+}
+
+#[hyperfold_engine::system]
+fn drag_carousel(drag: &Drag, carousels: Vec<CarouselData>) {
+    if let Some(CarouselData { carousel, .. }) = carousels.into_iter().find(|c| c.eid == &drag.eid)
+    {
+        carousel.scroll_by(-f32!(drag.mouse_dx));
+        carousel.v_scroll = -f32!(drag.mouse_dx) * 25.0;
+        carousel.drag_dist += f32!(drag.mouse_dx).abs() + f32!(drag.mouse_dy).abs();
+    }
+}
+
+// Momentum after releasing a drag, then settling on the nearest item
+#[hyperfold_engine::system]
+fn update_carousels(Update(dt): &Update, carousels: Vec<CarouselData>, drag_state: &DragState) {
+    let s = f32!(*dt) / 1000.0;
+    for CarouselData { carousel, eid } in carousels {
+        if drag_state.dragging(*eid) {
+            continue;
+        }
+
+        if let Some(snap) = &mut carousel.snap {
+            snap.t = (snap.t + f32!(*dt) / SNAP_MS).min(1.0);
+            let scroll = snap.from + (snap.to - snap.from) * ease_out_cubic(snap.t);
+            if snap.t >= 1.0 {
+                carousel.snap = None;
+            }
+            carousel.scroll = scroll;
+            carousel.update = true;
+            continue;
+        }
+
+        match carousel.v_scroll.abs() >= SNAP_SPEED && carousel.scroll_by(carousel.v_scroll * s) {
+            true => carousel.v_scroll *= 0.01_f32.powf(s),
+            false => {
+                carousel.v_scroll = 0.0;
+                if let Some(idx) = carousel.front() {
+                    if (carousel.stops[idx] - carousel.scroll).abs() >= 0.5 {
+                        carousel.scroll_to(idx);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,235 @@
+use hyperfold_engine::{
+    add_components, components,
+    ecs::{
+        entities::{Entity, NewEntity},
+        events::core::Update,
+    },
+    framework::{
+        event_system::events::Click,
+        physics::Position,
+        render_system::{
+            font::{FontData, TIMES},
+            render_data::{Fit, RenderAsset},
+            render_text::{RenderText, TextImage},
+            AssetManager, RenderComponent, RenderOpts, Renderer, Screen,
+        },
+    },
+    utils::{
+        colors::{TRANSPARENT, WHITE},
+        number::Number,
+        rect::{Align, Rect},
+        timer::{Timer, TimerTrait},
+        util::AsType,
+    },
+};
+
+use crate::{
+    _engine::{Components, Events},
+    crystal::CrystalNumbers,
+    equation,
+    layout::ScreenResized,
+    observers,
+    param_dag::{Node, NodeDefault, NodeTrait, NumDag, Observe, Observer, Root},
+    parameters,
+    save::SavedRoots,
+    utils::elevations::Elevations,
+};
+
+// Parameters
+parameters!(CatalystNumbers(Catalysts = 0, Prestiges = 0));
+// Potential: total catalysts earned from lifetime magic
+// Gain: catalysts received by prestiging now
+// Multiplier: permanent bonus to wizard power
+parameters!(CatalystNodes(Potential, Gain, Multiplier));
+
+#[hyperfold_engine::system(Init)]
+fn init_catalyst_numbers(dag: &mut NumDag, saved_roots: &mut SavedRoots) {
+    dag.0.add_node(
+        CatalystNodes::Potential,
+        // sqrt(lifetime / 1e6)
+        equation!(|(l: CrystalNumbers::LifetimeMagic,)| {
+            let log = f64::from((*l + 1.into()).log10());
+            Number::from(10_f64.powf((log - 6.0) / 2.0).floor())
+        }),
+    );
+    dag.0.add_node(
+        CatalystNodes::Gain,
+        equation!(|(p: CatalystNodes::Potential, c: CatalystNumbers::Catalysts,)| {
+            match *p > *c {
+                true => *p - *c,
+                false => 0.into(),
+            }
+        }),
+    );
+    dag.0.add_node(
+        CatalystNodes::Multiplier,
+        // +10% per catalyst
+        equation!(|(c: CatalystNumbers::Catalysts,)| *c * Number::from(0.1) + 1.into()),
+    );
+
+    saved_roots.add("catalysts", CatalystNumbers::Catalysts);
+    saved_roots.add("prestiges", CatalystNumbers::Prestiges);
+}
+
+// Resets magic in exchange for catalysts
+// Other systems listen to this to reset their own progress
+#[hyperfold_engine::event]
+struct Prestige;
+
+#[hyperfold_engine::system]
+fn prestige(_: &Prestige, dag: &mut NumDag) {
+    let gain = *dag.0.get(CatalystNodes::Gain);
+    dag.0.update(CatalystNumbers::Catalysts, |c| *c + gain);
+    dag.0.update(CatalystNumbers::Prestiges, |p| *p + 1.into());
+    dag.0.set(CrystalNumbers::Magic, 0.into());
+}
+
+// Prestige button
+#[hyperfold_engine::component(Singleton)]
+struct PrestigeButton {
+    // Clicking again while confirming performs the prestige
+    confirming: bool,
+}
+
+#[hyperfold_engine::component(Dummy)]
+struct PrestigeObservers;
+
+observers!(
+    PrestigeObservers<Number> {
+        gain = CatalystNodes::Gain
+    }
+);
+
+components!(
+    PrestigeButtonData,
+    button: &'a mut PrestigeButton,
+    timer: &'a mut Timer,
+    pos: &'a mut Position,
+    text: &'a mut RenderComponent,
+    observers: &'a mut PrestigeObservers
+);
+
+// How long the confirm prompt stays up
+const CONFIRM_MS: u32 = 3000;
+
+// Bottom center
+fn button_rect(w: f32, h: f32) -> Rect {
+    Rect::from(
+        w / 2.0,
+        h - 10.0,
+        w / 4.0,
+        30.0,
+        Align::Center,
+        Align::BotRight,
+    )
+}
+
+#[hyperfold_engine::system(Init)]
+fn init_prestige_button(
+    entities: &mut dyn Components,
+    r: &Renderer,
+    am: &mut AssetManager,
+    screen: &Screen,
+) {
+    let rect = button_rect(screen.0.w as f32, screen.0.h as f32);
+
+    let e = Entity::new();
+    add_components!(
+        entities,
+        e,
+        PrestigeButton { confirming: false },
+        Timer::new(CONFIRM_MS),
+        RenderOpts::new(Elevations::Upgrades as u8).absolute(),
+        RenderComponent::new(
+            RenderText::new(FontData {
+                w: None,
+                h: Some(rect.h as u32),
+                sample: "Confirm? +9.99e999".to_string(),
+                file: TIMES.to_string()
+            })
+            .with_text_align(Align::Center, Align::BotRight)
+            .with_text_color(WHITE)
+            .with_images(vec![TextImage::Render(RenderComponent::new(
+                RenderAsset::from_file("res/wizards/catalyst.png", r, am)
+            ))])
+            .with_background_color(TRANSPARENT)
+            .with_dest_fit(Fit::fit_height())
+            .with_dest_align(Align::Center, Align::BotRight)
+        ),
+        Position(rect),
+        PrestigeObservers::new(),
+    );
+}
+
+fn prestige_text(confirming: bool, gain: &Number) -> String {
+    match (confirming, *gain > 0.into()) {
+        (_, false) => "Catalyze: need more magic".to_string(),
+        (false, true) => format!("Catalyze: +{gain}[i]"),
+        (true, true) => format!("Confirm? +{gain}[i]"),
+    }
+}
+
+#[hyperfold_engine::system]
+fn click_prestige(
+    m: &Click,
+    PrestigeButtonData {
+        eid,
+        button,
+        timer,
+        text,
+        ..
+    }: PrestigeButtonData,
+    events: &mut dyn Events,
+    dag: &mut NumDag,
+) {
+    if !m.is_me(eid) {
+        return;
+    }
+
+    let gain = *dag.0.get(CatalystNodes::Gain);
+    if button.confirming {
+        events.new_event(Prestige);
+        button.confirming = false;
+    } else if gain > 0.into() {
+        button.confirming = true;
+        *timer = Timer::new(CONFIRM_MS);
+    }
+    text.try_as_mut(|text: &mut RenderText| {
+        text.set_text(&prestige_text(button.confirming, &gain))
+    });
+}
+
+#[hyperfold_engine::system]
+fn update_prestige_button(
+    dt: &Update,
+    PrestigeButtonData {
+        button,
+        timer,
+        text,
+        observers,
+        ..
+    }: PrestigeButtonData,
+    dag: &mut NumDag,
+) {
+    let mut changed = false;
+    if button.confirming && timer.add_time(dt.0) > 0 {
+        button.confirming = false;
+        changed = true;
+    }
+    observers.gain.check(&mut dag.0, |_| changed = true);
+
+    if changed {
+        let gain = *dag.0.get(CatalystNodes::Gain);
+        text.try_as_mut(|text: &mut RenderText| {
+            text.set_text(&prestige_text(button.confirming, &gain))
+        });
+    }
+}
+
+#[hyperfold_engine::system]
+fn layout_prestige_button(
+    ScreenResized { w, h }: &ScreenResized,
+    PrestigeButtonData { pos, .. }: PrestigeButtonData,
+) {
+    pos.0 = button_rect(*w, *h);
+}
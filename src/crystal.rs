@@ -24,9 +24,13 @@ use hyperfold_engine::{
 };
 
 use crate::{
+    layout::ScreenResized,
     observers,
-    param_dag::{NodeTrait, NumDag, Observe, Observer, Root},
+    param_dag::{Dag, NodeTrait, NumDag, Observe, Observer, Root},
     parameters,
+    save::SavedRoots,
+    upgrade_defs::UpgradeGroups,
+    upgrades::UpgradeOwner,
     utils::elevations::Elevations,
 };
 
@@ -39,7 +43,13 @@ pub fn crystal_radius(camera: &Camera) -> f32 {
 struct Crystal;
 
 // Parameters
-parameters!(CrystalNumbers(Magic = 0));
+parameters!(CrystalNumbers(Magic = 0, LifetimeMagic = 0));
+
+// All magic income goes through here so lifetime magic stays in sync
+pub fn gain_magic(dag: &mut Dag<Number>, amount: Number) {
+    dag.update(CrystalNumbers::Magic, |m| *m + amount);
+    dag.update(CrystalNumbers::LifetimeMagic, |m| *m + amount);
+}
 
 #[hyperfold_engine::component(Dummy)]
 struct CrystalTextObservers;
@@ -52,10 +62,52 @@ observers!(
 
 hyperfold_engine::components!(labels(Crystal), CrystalPos, pos: &'a physics::Position);
 
+// Dashed circle marking the crystal radius
+#[hyperfold_engine::component(Singleton)]
+struct CrystalBoundary;
+
+hyperfold_engine::components!(
+    labels(CrystalBoundary),
+    CrystalBoundaryData,
+    pos: &'a mut physics::Position,
+    tex: &'a mut RenderComponent
+);
+
+fn boundary_texture(r: &Renderer, rad: f32) -> Texture {
+    let diam = rad + rad;
+    let tex = Texture::new(r, diam as u32, diam as u32, TRANSPARENT);
+    tex.draw(
+        r,
+        &mut Circle::new()
+            .set_color(RED)
+            .set_center(Point {
+                x: rad as i32,
+                y: rad as i32,
+            })
+            .border(rad as u32, -3, false)
+            .dashed(20),
+    );
+    tex
+}
+
+// Magic text sits above the crystal
+fn text_rect(crystal: &Rect) -> Rect {
+    Rect::from(
+        crystal.cx(),
+        crystal.y,
+        crystal.w,
+        30.0,
+        Align::Center,
+        Align::BotRight,
+    )
+}
+
 // Crystal text components
 #[hyperfold_engine::component(Singleton)]
 struct CrystalText;
 
+hyperfold_engine::components!(labels(CrystalText), CrystalTextPos, pos: &'a physics::Position);
+
 hyperfold_engine::components!(
     labels(CrystalText),
     CrystalTextData,
@@ -72,7 +124,13 @@ fn init_crystal(
     am: &mut AssetManager,
     screen: &render_system::Screen,
     camera: &Camera,
+    saved_roots: &mut SavedRoots,
+    groups: &mut UpgradeGroups,
 ) {
+    saved_roots.add("magic", CrystalNumbers::Magic);
+    saved_roots.add("lifetime_magic", CrystalNumbers::LifetimeMagic);
+    groups.add("crystal", Crystal);
+
     // Crystal
     let cx = screen.0.w as f32 / 2.0;
     let cy = screen.0.h as f32 / 2.0;
@@ -91,17 +149,11 @@ fn init_crystal(
             am
         )),
         physics::Position(rect),
+        UpgradeOwner::new(Crystal),
     );
 
     // Magic text
-    let text_rect = Rect::from(
-        rect.cx(),
-        rect.y,
-        rect.w,
-        30.0,
-        Align::Center,
-        Align::BotRight,
-    );
+    let text_rect = text_rect(&rect);
 
     let e = Entity::new();
     hyperfold_engine::add_components!(
@@ -133,23 +185,13 @@ fn init_crystal(
     // Boundary circle
     let rad = crystal_radius(camera);
     let diam = rad + rad;
-    let tex = Texture::new(r, diam as u32, diam as u32, TRANSPARENT);
-    tex.draw(
-        r,
-        &mut Circle::new()
-            .set_color(RED)
-            .set_center(Point {
-                x: rad as i32,
-                y: rad as i32,
-            })
-            .border(rad as u32, -3, false)
-            .dashed(20),
-    );
+    let tex = boundary_texture(r, rad);
 
     let e = Entity::new();
     hyperfold_engine::add_components!(
         entities,
         e,
+        CrystalBoundary,
         render_system::RenderOpts::new(Elevations::Background as u8),
         RenderComponent::new(RenderTexture::new(Some(tex))),
         physics::Position(
@@ -159,6 +201,29 @@ fn init_crystal(
     );
 }
 
+// The boundary follows the camera size and the text follows the crystal
+#[hyperfold_engine::system]
+fn layout_crystal(
+    _: &ScreenResized,
+    CrystalPos { pos, .. }: CrystalPos,
+    CrystalTextData { pos: text_pos, .. }: CrystalTextData,
+    CrystalBoundaryData {
+        pos: boundary_pos,
+        tex,
+        ..
+    }: CrystalBoundaryData,
+    camera: &Camera,
+    r: &Renderer,
+) {
+    let rad = crystal_radius(camera);
+    tex.set(RenderTexture::new(Some(boundary_texture(r, rad))));
+    boundary_pos.0 = pos
+        .0
+        .clone()
+        .with_dim(rad + rad, rad + rad, Align::Center, Align::Center);
+    text_pos.0 = text_rect(&pos.0);
+}
+
 #[hyperfold_engine::system]
 fn update_crystal_text(
     _: &Update,
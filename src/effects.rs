@@ -0,0 +1,320 @@
+use std::f32::consts::PI;
+
+use hyperfold_engine::{
+    add_components, components,
+    ecs::{
+        entities::{Entity, EntityTrash, NewEntity},
+        events::core::Update,
+    },
+    framework::{
+        physics::Position,
+        render_system::{
+            font::{FontData, TIMES},
+            render_data::{Fit, RenderTexture},
+            render_text::RenderText,
+            RenderComponent, RenderOpts, Renderer, Texture,
+        },
+    },
+    sdl2::SDL_Color,
+    utils::{
+        colors::TRANSPARENT,
+        rect::{Align, PointF, Rect},
+        timer::{Timer, TimerTrait},
+    },
+};
+
+use crate::{_engine::Components, utils::elevations::Elevations};
+
+// Progress of a timed effect
+#[derive(Copy, Clone)]
+struct Anim {
+    t: u32,
+    duration: u32,
+}
+
+impl Anim {
+    fn new(duration: u32) -> Self {
+        Self { t: 0, duration }
+    }
+
+    // Returns true once finished
+    fn advance(&mut self, dt: u32) -> bool {
+        self.t = (self.t + dt).min(self.duration);
+        self.t >= self.duration
+    }
+
+    // From 0 to 1
+    fn frac(&self) -> f32 {
+        self.t as f32 / self.duration.max(1) as f32
+    }
+}
+
+fn same_rect(a: &Rect, b: &Rect) -> bool {
+    a.x == b.x && a.y == b.y && a.w == b.w && a.h == b.h
+}
+
+// Scale and offset applied on top of wherever the entity is placed
+// Required by Pulse, Shake and Flash
+#[hyperfold_engine::component]
+struct UiTransform {
+    scale: f32,
+    offset: PointF,
+    // Rect before and after the transform was last applied
+    base: Option<(Rect, Rect)>,
+}
+
+impl UiTransform {
+    pub fn new() -> Self {
+        Self {
+            scale: 1.0,
+            offset: PointF { x: 0.0, y: 0.0 },
+            base: None,
+        }
+    }
+
+    fn is_identity(&self) -> bool {
+        self.scale == 1.0 && self.offset.x == 0.0 && self.offset.y == 0.0
+    }
+}
+
+// Grows and shrinks back once
+#[hyperfold_engine::component]
+struct Pulse {
+    // Peak scale
+    scale: f32,
+    anim: Option<Anim>,
+}
+
+impl Pulse {
+    pub fn new(scale: f32) -> Self {
+        Self { scale, anim: None }
+    }
+
+    pub fn start(&mut self, ms: u32) {
+        self.anim = Some(Anim::new(ms));
+    }
+}
+
+// Decaying side to side wobble
+#[hyperfold_engine::component]
+struct Shake {
+    // Peak displacement in pixels
+    amplitude: f32,
+    anim: Option<Anim>,
+}
+
+impl Shake {
+    pub fn new(amplitude: f32) -> Self {
+        Self {
+            amplitude,
+            anim: None,
+        }
+    }
+
+    pub fn start(&mut self, ms: u32) {
+        self.anim = Some(Anim::new(ms));
+    }
+}
+
+components!(
+    Pulses,
+    pulse: &'a mut Pulse,
+    transform: &'a mut UiTransform
+);
+components!(
+    Shakes,
+    shake: &'a mut Shake,
+    transform: &'a mut UiTransform
+);
+components!(
+    UiTransforms,
+    transform: &'a mut UiTransform,
+    pos: &'a mut Position
+);
+
+#[hyperfold_engine::system]
+fn update_pulses(Update(dt): &Update, pulses: Vec<Pulses>) {
+    for Pulses {
+        pulse, transform, ..
+    } in pulses
+    {
+        if let Some(anim) = &mut pulse.anim {
+            let done = anim.advance(*dt);
+            transform.scale = 1.0 + (pulse.scale - 1.0) * (anim.frac() * PI).sin();
+            if done {
+                pulse.anim = None;
+                transform.scale = 1.0;
+            }
+        }
+    }
+}
+
+#[hyperfold_engine::system]
+fn update_shakes(Update(dt): &Update, shakes: Vec<Shakes>) {
+    // Full wobbles per shake
+    const CYCLES: f32 = 3.0;
+
+    for Shakes {
+        shake, transform, ..
+    } in shakes
+    {
+        if let Some(anim) = &mut shake.anim {
+            let done = anim.advance(*dt);
+            let t = anim.frac();
+            transform.offset.x = shake.amplitude * (1.0 - t) * (t * CYCLES * 2.0 * PI).sin();
+            if done {
+                shake.anim = None;
+                transform.offset.x = 0.0;
+            }
+        }
+    }
+}
+
+#[hyperfold_engine::system]
+fn apply_ui_transforms(_: &Update, transforms: Vec<UiTransforms>) {
+    for UiTransforms { transform, pos, .. } in transforms {
+        // Anything that moved the entity since last frame sets a new base
+        let base = match &transform.base {
+            Some((base, applied)) if same_rect(applied, &pos.0) => base.clone(),
+            _ => pos.0.clone(),
+        };
+        if transform.is_identity() {
+            pos.0 = base;
+            transform.base = None;
+            continue;
+        }
+
+        let mut rect = base.clone().with_dim(
+            base.w * transform.scale,
+            base.h * transform.scale,
+            Align::Center,
+            Align::Center,
+        );
+        rect.x += transform.offset.x;
+        rect.y += transform.offset.y;
+        pos.0 = rect.clone();
+        transform.base = Some((base, rect));
+    }
+}
+
+// Colored overlay that shrinks away, drawn above upgrade icons in screen coordinates
+#[hyperfold_engine::component]
+struct Flash {
+    anim: Anim,
+}
+
+components!(
+    Flashes,
+    flash: &'a mut Flash,
+    transform: &'a mut UiTransform
+);
+
+pub fn spawn_flash(
+    entities: &mut dyn Components,
+    r: &Renderer,
+    rect: Rect,
+    color: SDL_Color,
+    ms: u32,
+) {
+    let e = Entity::new();
+    add_components!(
+        entities,
+        e,
+        Flash {
+            anim: Anim::new(ms)
+        },
+        Position(rect),
+        RenderOpts::new(Elevations::UpgradeBadges as u8).absolute(),
+        // Stretched over the rect, so the texture never needs to be recreated
+        RenderComponent::new(RenderTexture::new(Some(Texture::new(r, 1, 1, color)))),
+        UiTransform::new(),
+    );
+}
+
+#[hyperfold_engine::system]
+fn update_flashes(Update(dt): &Update, flashes: Vec<Flashes>, trash: &mut EntityTrash) {
+    for Flashes {
+        eid,
+        flash,
+        transform,
+    } in flashes
+    {
+        if flash.anim.advance(*dt) {
+            trash.0.push(*eid);
+            continue;
+        }
+        transform.scale = 1.0 - flash.anim.frac();
+    }
+}
+
+// Text that drifts upwards, removed when its Timer runs out
+#[hyperfold_engine::component]
+struct FloatingText {
+    // Pixels per second
+    speed: f32,
+}
+
+components!(
+    FloatingTexts,
+    text: &'a FloatingText,
+    pos: &'a mut Position,
+    timer: &'a mut Timer
+);
+
+// Position is in world coordinates, centered on pos
+pub fn spawn_floating_text(
+    entities: &mut dyn Components,
+    text: &str,
+    pos: PointF,
+    color: SDL_Color,
+    ms: u32,
+) {
+    const H: f32 = 24.0;
+
+    let e = Entity::new();
+    add_components!(
+        entities,
+        e,
+        FloatingText { speed: 40.0 },
+        Position(Rect::from(
+            pos.x,
+            pos.y,
+            H * 8.0,
+            H,
+            Align::Center,
+            Align::Center
+        )),
+        RenderOpts::new(Elevations::Popups as u8),
+        RenderComponent::new(
+            RenderText::new(FontData {
+                w: None,
+                h: Some(H as u32),
+                sample: "-9.99e999".to_string(),
+                file: TIMES.to_string()
+            })
+            .with_text_align(Align::Center, Align::Center)
+            .with_text_color(color)
+            .with_text(text)
+            .with_background_color(TRANSPARENT)
+            .with_dest_fit(Fit::fit_height())
+            .with_dest_align(Align::Center, Align::Center)
+        ),
+        Timer::new(ms),
+    );
+}
+
+#[hyperfold_engine::system]
+fn update_floating_texts(Update(dt): &Update, texts: Vec<FloatingTexts>, trash: &mut EntityTrash) {
+    for FloatingTexts {
+        eid,
+        text,
+        pos,
+        timer,
+    } in texts
+    {
+        pos.0.y -= text.speed * *dt as f32 / 1000.0;
+        if timer.add_time(*dt) > 0 {
+            trash.0.push(*eid);
+        }
+    }
+}
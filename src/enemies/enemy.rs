@@ -1,3 +1,5 @@
+use std::ops::{Range, RangeInclusive};
+
 use hyperfold_engine::{
     _engine::Entity,
     add_components, components,
@@ -15,29 +17,59 @@ use hyperfold_engine::{
     utils::rect::{Align, PointF, Rect},
 };
 use itertools::Itertools;
+use rand::Rng;
 
 use crate::{
-    _engine::Components,
+    _engine::{Components, Events},
     crystal::{crystal_radius, CrystalPos},
+    health::DamageWizard,
+    input::Paused,
     utils::elevations::Elevations,
     wizard::WizardPos,
 };
 
 #[hyperfold_engine::component]
-struct Enemy;
+struct Enemy {
+    // Hits left
+    pub hp: u32,
+    // Dealt to the wizard on contact, also how threatening the enemy is
+    pub damage: f32,
+}
+
+// Stats are rolled per enemy, tougher and more dangerous enemies make the
+// target policies matter
+#[hyperfold_engine::global]
+struct EnemyConfig {
+    pub hp: RangeInclusive<u32>,
+    pub damage: Range<f32>,
+}
+
+impl EnemyConfig {
+    pub fn new() -> Self {
+        Self {
+            hp: 1..=3,
+            damage: 5.0..15.0,
+        }
+    }
+}
 
 pub fn spawn_enemy(
     pos: PointF,
+    config: &EnemyConfig,
     entities: &mut dyn Components,
     r: &Renderer,
     am: &mut AssetManager,
 ) {
+    let mut rand = rand::thread_rng();
     let e = Entity::new();
     let anim = Animation::new(8, 100);
     add_components!(
         entities,
         e,
-        Enemy,
+        Enemy {
+            hp: rand.gen_range(config.hp.clone()),
+            damage: rand.gen_range(config.damage.clone())
+        },
         RenderOpts::new(Elevations::Enemies as u8),
         RenderComponent::new(
             RenderAsset::new(
@@ -69,10 +101,11 @@ pub fn spawn_enemy(
 struct TargetEnemy;
 
 components!(labels(TargetEnemy), EnemyProjectiles, pos: &'a Position);
-components!(labels(Enemy), Enemies, pos: &'a Position);
+components!(Enemies, enemy: &'a Enemy, pos: &'a Position);
+components!(EnemiesMut, enemy: &'a mut Enemy, pos: &'a Position);
 components!(
-    labels(Enemy),
     UpdateEnemies,
+    enemy: &'a Enemy,
     pos: &'a Position,
     pd: &'a mut PhysicsData
 );
@@ -85,9 +118,12 @@ fn update_enemies(
     crystal: CrystalPos,
     camera: &Camera,
     trash: &mut EntityTrash,
+    events: &mut dyn Events,
+    paused: &Paused,
 ) {
     const SPEED: f32 = 125.0;
-    match wizard.pos.0.center().dist(crystal.pos.0.center()) <= crystal_radius(camera) {
+    let in_crystal = wizard.pos.0.center().dist(crystal.pos.0.center()) <= crystal_radius(camera);
+    match paused.0 || in_crystal {
         true => enemies.into_iter().for_each(|enemy| {
             enemy.pd.v = PointF::new();
             enemy.pd.a = PointF::new();
@@ -97,8 +133,10 @@ fn update_enemies(
             let delta = target - enemy.pos.0.center();
             let mag = delta.mag();
             match mag <= 25.0 {
+                // Enemies are used up on contact
                 true => {
                     trash.0.push(*enemy.eid);
+                    events.new_event(DamageWizard(enemy.enemy.damage));
                 }
                 false => {
                     enemy.pd.v = PointF {
@@ -115,16 +153,20 @@ fn update_enemies(
 fn fb_hit_enemy(
     _: &Update,
     mut projs: Vec<EnemyProjectiles>,
-    enemies: Vec<Enemies>,
+    enemies: Vec<EnemiesMut>,
     trash: &mut EntityTrash,
 ) {
-    for enemy in enemies {
+    for EnemiesMut { eid, enemy, pos } in enemies {
         if let Some((i, proj)) = projs
             .iter()
-            .find_position(|proj| enemy.pos.0.intersects(&proj.pos.0))
+            .find_position(|proj| pos.0.intersects(&proj.pos.0))
         {
-            trash.0.extend([enemy.eid, proj.eid]);
+            trash.0.push(*proj.eid);
             projs.swap_remove(i);
+            enemy.hp = enemy.hp.saturating_sub(1);
+            if enemy.hp == 0 {
+                trash.0.push(*eid);
+            }
         }
     }
 }
@@ -18,11 +18,12 @@ use rand::Rng;
 use crate::{
     _engine::Components,
     crystal::{crystal_radius, CrystalPos},
+    input::Paused,
     utils::rand_sign::RandSign,
     wizard::WizardPos,
 };
 
-use super::enemy::spawn_enemy;
+use super::enemy::{spawn_enemy, EnemyConfig};
 
 #[hyperfold_engine::component(Singleton)]
 struct EnemySpawner;
@@ -49,9 +50,11 @@ fn enemy_spawner_timer(
     r: &Renderer,
     am: &mut AssetManager,
     camera: &Camera,
+    config: &EnemyConfig,
+    paused: &Paused,
 ) {
     let crys_rad = crystal_radius(camera);
-    if wizard.pos.0.center().dist(crystal.pos.0.center()) <= crys_rad {
+    if paused.0 || wizard.pos.0.center().dist(crystal.pos.0.center()) <= crys_rad {
         return;
     }
 
@@ -70,7 +73,7 @@ fn enemy_spawner_timer(
                 pos.x = wiz_x - (pos.x - wiz_x);
                 pos.y = wiz_y - (pos.y - wiz_y);
             }
-            spawn_enemy(pos, entities, r, am);
+            spawn_enemy(pos, config, entities, r, am);
         }
     }
 }
@@ -14,20 +14,28 @@ use hyperfold_engine::{
 };
 
 use crate::{
-    crystal::{CrystalNumbers, CrystalPos},
+    _engine::Events,
+    crystal::{gain_magic, CrystalPos},
+    enemies::enemy::{Enemies, TargetEnemy},
+    input::Paused,
     param_dag::NumDag,
+    targeting::Targeting,
     utils::elevations::Elevations,
+    wizard::WizardNumbers,
 };
 
 #[hyperfold_engine::component]
 struct Fireball {
     pub value: Number,
+    // Homing fireballs chase an enemy instead of flying to the crystal
+    pub target: Option<Entity>,
 }
 
 #[hyperfold_engine::event]
 struct CreateFireball {
     pub pos: PointF,
     pub value: Number,
+    pub target: Option<Entity>,
 }
 
 #[hyperfold_engine::system]
@@ -41,10 +49,16 @@ pub fn new_fireball(
     hyperfold_engine::add_components!(
         entities,
         e,
-        Fireball { value: data.value },
+        Fireball {
+            value: data.value,
+            target: data.target
+        },
         render_system::RenderOpts::new(Elevations::Projectiles as u8),
         render_system::RenderComponent::new(RenderAsset::from_file(
-            "res/projectiles/fireball.png",
+            match data.target {
+                Some(_) => "res/projectiles/fireball2.png",
+                None => "res/projectiles/fireball.png",
+            },
             r,
             am
         )),
@@ -60,33 +74,66 @@ pub fn new_fireball(
             boundary: None
         },
     );
+    if data.target.is_some() {
+        hyperfold_engine::add_components!(entities, e, TargetEnemy);
+    }
 }
 
 hyperfold_engine::components!(
     UpdateFireball,
     pos: &'a physics::Position,
     pd: &'a mut physics::PhysicsData,
-    fb: &'a Fireball
+    fb: &'a mut Fireball
 );
 
 #[hyperfold_engine::system]
 fn update_fireball(
     _e: &events::core::Update,
+    events: &mut dyn Events,
     trash: &mut EntityTrash,
     fballs: Vec<UpdateFireball>,
+    enemies: Vec<Enemies>,
     crystal: CrystalPos,
+    targeting: &Targeting,
+    paused: &Paused,
     dag: &mut NumDag,
 ) {
+    let speed = f64::from(*dag.0.get(WizardNumbers::FireballSpeed)) as f32;
+    let find = |t: Option<Entity>| t.and_then(|t| enemies.iter().find(|e| e.eid == &t));
     for UpdateFireball { eid, pos, pd, fb } in fballs {
-        let target = crystal.pos.0.center();
+        if paused.0 {
+            pd.v = PointF::new();
+            continue;
+        }
+        let mut enemy = find(fb.target);
+        // Picks a new enemy when the target is gone
+        if fb.target.is_some() && enemy.is_none() {
+            fb.target = targeting.pick(pos.0.center(), &enemies);
+            enemy = find(fb.target);
+            // With none left it is swapped for a plain fireball that heads to the crystal
+            if fb.target.is_none() {
+                trash.0.push(*eid);
+                events.new_event(CreateFireball {
+                    pos: pos.0.center(),
+                    value: fb.value,
+                    target: None,
+                });
+                continue;
+            }
+        }
+        let target = match enemy {
+            Some(enemy) => enemy.pos.0.center(),
+            None => crystal.pos.0.center(),
+        };
         let (dx, dy) = (target.x - pos.0.cx(), target.y - pos.0.cy());
         let mag = (dx * dx + dy * dy).sqrt();
-        if mag <= 5.0 {
+        // Homing fireballs are removed when they hit, see fb_hit_enemy
+        if fb.target.is_none() && mag <= 5.0 {
             trash.0.push(*eid);
-            dag.0.update(CrystalNumbers::Magic, |m| *m + fb.value);
-        } else {
-            pd.v.x = dx * 150.0 / mag;
-            pd.v.y = dy * 150.0 / mag;
+            gain_magic(&mut dag.0, fb.value);
+        } else if mag > 0.0 {
+            pd.v.x = dx * speed / mag;
+            pd.v.y = dy * speed / mag;
         }
     }
 }
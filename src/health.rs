@@ -0,0 +1,244 @@
+use hyperfold_engine::{
+    add_components, components,
+    ecs::{
+        entities::{Entity, NewEntity},
+        events::core::Update,
+    },
+    framework::{
+        physics::Position,
+        render_system::{
+            render_data::RenderTexture, Camera, RenderComponent, RenderOpts, Renderer, Texture,
+        },
+    },
+    sdl2::SDL_Color,
+    utils::{
+        colors::{RED, WHITE},
+        number::Number,
+        rect::{Align, PointF, Rect},
+    },
+};
+
+use crate::{
+    _engine::Components,
+    crystal::{crystal_radius, CrystalPos},
+    effects::spawn_floating_text,
+    equation,
+    input::Paused,
+    param_dag::{Node, NodeDefault, NodeTrait, NumDag, Root},
+    parameters,
+    save::SavedRoots,
+    stats::{Stat, StatAdd, StatMult},
+    utils::elevations::Elevations,
+    wizard::{WizardNumbers, WizardPos, WizardPosMut},
+};
+
+// Hp: current health of the player's wizard
+// FireRatePenalty: multiplier on the fire rate, lowered for a while after dying
+parameters!(WizardHealth(Hp = 100, FireRatePenalty = 1));
+// PlayerFireRate: fire rate of the player's wizard, with the penalty applied
+parameters!(HealthNodes(MaxHp, PlayerFireRate));
+
+const BASE_MAX_HP: f64 = 100.0;
+// Fraction of max HP regenerated per second inside the crystal radius
+const REGEN: f64 = 0.2;
+const PENALTY: f64 = 0.5;
+const PENALTY_MS: u32 = 15000;
+
+const BACK_COLOR: SDL_Color = SDL_Color {
+    r: 64,
+    g: 0,
+    b: 0,
+    a: 200,
+};
+const FILL_COLOR: SDL_Color = SDL_Color {
+    r: 0,
+    g: 200,
+    b: 64,
+    a: 255,
+};
+
+#[hyperfold_engine::system(Init)]
+fn init_health_numbers(dag: &mut NumDag, saved_roots: &mut SavedRoots) {
+    dag.0.add_node(
+        HealthNodes::MaxHp,
+        equation!(
+            |(a: StatAdd(Stat::MaxHp), m: StatMult(Stat::MaxHp),)| (*a + BASE_MAX_HP.into()) * *m
+        ),
+    );
+    dag.0.add_node(
+        HealthNodes::PlayerFireRate,
+        equation!(
+            |(r: WizardNumbers::FireRate, p: WizardHealth::FireRatePenalty,)| *r * *p
+        ),
+    );
+
+    saved_roots.add("wizard_hp", WizardHealth::Hp);
+}
+
+// Time left on the fire rate penalty from dying
+#[hyperfold_engine::global]
+struct Knockout {
+    pub penalty_ms: u32,
+    // Set on knockout so the rest of the frame's damage is ignored, cleared next update
+    pub immune: bool,
+}
+
+impl Knockout {
+    pub fn new() -> Self {
+        Self {
+            penalty_ms: 0,
+            immune: false,
+        }
+    }
+}
+
+// Damage to the player's wizard, e.g. from enemies touching it
+#[hyperfold_engine::event]
+struct DamageWizard(pub f32);
+
+#[hyperfold_engine::system]
+fn damage_wizard(
+    DamageWizard(amount): &DamageWizard,
+    WizardPosMut { pos, .. }: WizardPosMut,
+    CrystalPos { pos: crys_pos, .. }: CrystalPos,
+    knockout: &mut Knockout,
+    entities: &mut dyn Components,
+    dag: &mut NumDag,
+) {
+    if knockout.immune {
+        return;
+    }
+
+    let p = pos.0.center();
+    spawn_floating_text(
+        entities,
+        &format!("-{}", amount.round()),
+        PointF {
+            x: p.x,
+            y: p.y - pos.0.h / 2.0,
+        },
+        RED,
+        1000,
+    );
+
+    dag.0
+        .update(WizardHealth::Hp, |hp| *hp - Number::from(*amount as f64));
+    if *dag.0.get(WizardHealth::Hp) > 0.into() {
+        return;
+    }
+
+    // Back to the crystal at full health, but slower for a while
+    let c = crys_pos.0.center();
+    pos.0.set_pos(c.x, c.y, Align::Center, Align::Center);
+    let max_hp = *dag.0.get(HealthNodes::MaxHp);
+    dag.0.set(WizardHealth::Hp, max_hp);
+    dag.0.set(WizardHealth::FireRatePenalty, PENALTY.into());
+    knockout.penalty_ms = PENALTY_MS;
+    knockout.immune = true;
+    spawn_floating_text(
+        entities,
+        "Knocked out!",
+        PointF {
+            x: c.x,
+            y: c.y - pos.0.h,
+        },
+        WHITE,
+        2000,
+    );
+}
+
+#[hyperfold_engine::system]
+fn update_health(
+    Update(dt): &Update,
+    WizardPos { pos, .. }: WizardPos,
+    CrystalPos { pos: crys_pos, .. }: CrystalPos,
+    camera: &Camera,
+    knockout: &mut Knockout,
+    paused: &Paused,
+    dag: &mut NumDag,
+) {
+    knockout.immune = false;
+    if paused.0 {
+        return;
+    }
+    if knockout.penalty_ms > 0 {
+        knockout.penalty_ms = knockout.penalty_ms.saturating_sub(*dt);
+        if knockout.penalty_ms == 0 {
+            dag.0.set(WizardHealth::FireRatePenalty, 1.into());
+        }
+    }
+
+    let (hp, max_hp) = (*dag.0.get(WizardHealth::Hp), *dag.0.get(HealthNodes::MaxHp));
+    let regen = match pos.0.center().dist(crys_pos.0.center()) <= crystal_radius(camera) {
+        true => max_hp * Number::from(REGEN * *dt as f64 / 1000.0),
+        false => 0.into(),
+    };
+    // Also clamps HP when max HP goes down, e.g. after a respec
+    let new_hp = match hp + regen < max_hp {
+        true => hp + regen,
+        false => max_hp,
+    };
+    if new_hp != hp {
+        dag.0.set(WizardHealth::Hp, new_hp);
+    }
+}
+
+// Background and fill of the bar above the wizard, shown while hurt
+#[hyperfold_engine::component]
+struct HealthBar {
+    fill: bool,
+}
+
+#[hyperfold_engine::system(Init)]
+fn init_health_bar(entities: &mut dyn Components, r: &Renderer) {
+    for (fill, color) in [(false, BACK_COLOR), (true, FILL_COLOR)] {
+        let e = Entity::new();
+        add_components!(
+            entities,
+            e,
+            HealthBar { fill },
+            Position(Rect::new()),
+            RenderOpts::new(Elevations::HealthBars as u8).is_visible(false),
+            RenderComponent::new(RenderTexture::new(Some(Texture::new(r, 1, 1, color)))),
+        );
+    }
+}
+
+components!(
+    HealthBars,
+    bar: &'a HealthBar,
+    pos: &'a mut Position,
+    opts: &'a mut RenderOpts
+);
+
+#[hyperfold_engine::system]
+fn update_health_bar(
+    _: &Update,
+    bars: Vec<HealthBars>,
+    WizardPos { pos: wiz_pos, .. }: WizardPos,
+    dag: &mut NumDag,
+) {
+    const H: f32 = 8.0;
+
+    let (hp, max_hp) = (
+        f64::from(*dag.0.get(WizardHealth::Hp)),
+        f64::from(*dag.0.get(HealthNodes::MaxHp)),
+    );
+    let frac = (hp / max_hp.max(1.0)).clamp(0.0, 1.0) as f32;
+    let rect = &wiz_pos.0;
+    for HealthBars { bar, pos, opts, .. } in bars {
+        opts.set_visible(frac < 1.0);
+        let w = match bar.fill {
+            true => rect.w * frac,
+            false => rect.w,
+        };
+        pos.0 = Rect::from(
+            rect.x,
+            rect.y - H - 4.0,
+            w,
+            H,
+            Align::TopLeft,
+            Align::TopLeft,
+        );
+    }
+}
@@ -0,0 +1,170 @@
+use std::f32::consts::TAU;
+
+use hyperfold_engine::{
+    add_components, components,
+    ecs::{
+        entities::{Entity, EntityTrash, NewEntity},
+        events::core::Update,
+    },
+    framework::{
+        event_system::events::Click,
+        physics::Position,
+        render_system::{
+            font::{FontData, TIMES},
+            render_data::Fit,
+            render_text::RenderText,
+            Camera, RenderComponent, RenderOpts,
+        },
+    },
+    utils::{
+        colors::{GRAY, WHITE},
+        number::Number,
+        rect::{Align, PointF, Rect},
+        util::AsType,
+    },
+};
+
+use crate::{
+    _engine::{Components, Events},
+    archetypes::{WizardCount, WizardType},
+    catalyst::Prestige,
+    crystal::{crystal_radius, CrystalNumbers, CrystalPos},
+    layout::LayoutSize,
+    param_dag::{Dag, NumDag, Root},
+    utils::elevations::Elevations,
+    wizard::{HiredWizards, SpawnWizard},
+};
+
+// Wizards of the type bought so far
+fn hired(ty: WizardType, dag: &mut Dag<Number>) -> u32 {
+    let count = WizardCount(ty);
+    (f64::from(*dag.get(count)) - f64::from(Root::default(&count))) as u32
+}
+
+fn hire_cost(ty: WizardType, dag: &mut Dag<Number>) -> Number {
+    ty.archetype().hire_cost.at(hired(ty, dag))
+}
+
+// Buttons in the bottom left, one per wizard type once revealed
+#[hyperfold_engine::component]
+struct HireButton {
+    ty: WizardType,
+    text: String,
+}
+
+#[hyperfold_engine::system(Init)]
+fn init_hire_buttons(entities: &mut dyn Components) {
+    for ty in WizardType::ALL {
+        let e = Entity::new();
+        add_components!(
+            entities,
+            e,
+            HireButton {
+                ty,
+                text: String::new()
+            },
+            Position(Rect::new()),
+            RenderOpts::new(Elevations::Upgrades as u8)
+                .absolute()
+                .is_visible(false),
+            RenderComponent::new(
+                RenderText::new(FontData {
+                    w: None,
+                    h: Some(25),
+                    sample: "Hire Catalyst Wizard: 9.99e999".to_string(),
+                    file: TIMES.to_string()
+                })
+                .with_text_color(WHITE)
+                .with_background_color(GRAY)
+                .with_dest_fit(Fit::fit_height())
+                .with_dest_align(Align::TopLeft, Align::BotRight)
+            )
+        );
+    }
+}
+
+components!(
+    HireButtons,
+    button: &'a mut HireButton,
+    pos: &'a mut Position,
+    opts: &'a mut RenderOpts,
+    text: &'a mut RenderComponent
+);
+
+#[hyperfold_engine::system]
+fn update_hire_buttons(_: &Update, buttons: Vec<HireButtons>, size: &LayoutSize, dag: &mut NumDag) {
+    const H: f32 = 30.0;
+
+    // Stacked upwards in type order
+    let mut y = size.h as f32 - 10.0;
+    for b in buttons {
+        let revealed = b.button.ty.archetype().reveal.met(&mut dag.0);
+        b.opts.set_visible(revealed);
+        if !revealed {
+            continue;
+        }
+
+        b.pos.0 = Rect::from(10.0, y, 300.0, H, Align::TopLeft, Align::BotRight);
+        y -= H + 5.0;
+        let text = format!(
+            "Hire {}: {}",
+            b.button.ty.archetype().name,
+            hire_cost(b.button.ty, &mut dag.0)
+        );
+        if text != b.button.text {
+            b.text.try_as_mut(|t: &mut RenderText| t.set_text(&text));
+            b.button.text = text;
+        }
+    }
+}
+
+#[hyperfold_engine::system]
+fn click_hire(
+    m: &Click,
+    buttons: Vec<HireButtons>,
+    CrystalPos { pos, .. }: CrystalPos,
+    camera: &Camera,
+    events: &mut dyn Events,
+    dag: &mut NumDag,
+) {
+    let ty = match buttons.into_iter().find(|b| m.is_me(b.eid)) {
+        Some(b) => b.button.ty,
+        None => return,
+    };
+
+    let cost = hire_cost(ty, &mut dag.0);
+    if *dag.0.get(CrystalNumbers::Magic) < cost {
+        return;
+    }
+    dag.0.update(CrystalNumbers::Magic, |m| *m - cost);
+    dag.0.update(WizardCount(ty), |c| *c + 1.into());
+
+    // New hires line up around the crystal, then can be dragged anywhere
+    let a = TAU * (hired(ty, &mut dag.0) as f32 / 8.0 + ty as u8 as f32 / 3.0);
+    let r = crystal_radius(camera) * 0.6;
+    let c = pos.0.center();
+    events.new_event(SpawnWizard {
+        ty,
+        pos: PointF {
+            x: c.x + r * a.cos(),
+            y: c.y + r * a.sin(),
+        },
+    });
+}
+
+// Hired wizards are lost when catalyzing
+#[hyperfold_engine::system]
+fn reset_hires(
+    _: &Prestige,
+    wizards: Vec<HiredWizards>,
+    trash: &mut EntityTrash,
+    dag: &mut NumDag,
+) {
+    for w in wizards {
+        trash.0.push(*w.eid);
+    }
+    for ty in WizardType::ALL {
+        let count = WizardCount(ty);
+        dag.0.set(count, Root::default(&count));
+    }
+}
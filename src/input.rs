@@ -0,0 +1,250 @@
+use std::fs;
+
+use hyperfold_engine::{
+    framework::event_system::events::{FocusLost, Key},
+    sdl2::SDL_KeyCode::{self, *},
+};
+
+use crate::_engine::Events;
+
+const CONTROLS_FILE: &str = "res/controls.txt";
+
+// Everything the player can do from the keyboard, bound to keys in CONTROLS_FILE
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    // Opens or closes the player's upgrades
+    OpenUpgrades,
+    PrevUpgrade,
+    NextUpgrade,
+    CloseUpgrades,
+    // See autobuy::prioritize_upgrade
+    Prioritize,
+    CycleTarget,
+    // Stops the game world, see Paused
+    Pause,
+    // See spells::Spell
+    Ability1,
+    Ability2,
+    Ability3,
+}
+
+impl Action {
+    pub const ALL: [Action; 14] = [
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::OpenUpgrades,
+        Action::PrevUpgrade,
+        Action::NextUpgrade,
+        Action::CloseUpgrades,
+        Action::Prioritize,
+        Action::CycleTarget,
+        Action::Pause,
+        Action::Ability1,
+        Action::Ability2,
+        Action::Ability3,
+    ];
+
+    // Used in the controls file
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::MoveUp => "move_up",
+            Action::MoveDown => "move_down",
+            Action::MoveLeft => "move_left",
+            Action::MoveRight => "move_right",
+            Action::OpenUpgrades => "open_upgrades",
+            Action::PrevUpgrade => "prev_upgrade",
+            Action::NextUpgrade => "next_upgrade",
+            Action::CloseUpgrades => "close_upgrades",
+            Action::Prioritize => "prioritize",
+            Action::CycleTarget => "cycle_target",
+            Action::Pause => "pause",
+            Action::Ability1 => "ability_1",
+            Action::Ability2 => "ability_2",
+            Action::Ability3 => "ability_3",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|a| a.name() == name)
+    }
+}
+
+fn key_from_name(name: &str) -> Option<SDL_KeyCode> {
+    Some(match name {
+        "a" => SDLK_a,
+        "b" => SDLK_b,
+        "c" => SDLK_c,
+        "d" => SDLK_d,
+        "e" => SDLK_e,
+        "f" => SDLK_f,
+        "g" => SDLK_g,
+        "h" => SDLK_h,
+        "i" => SDLK_i,
+        "j" => SDLK_j,
+        "k" => SDLK_k,
+        "l" => SDLK_l,
+        "m" => SDLK_m,
+        "n" => SDLK_n,
+        "o" => SDLK_o,
+        "p" => SDLK_p,
+        "q" => SDLK_q,
+        "r" => SDLK_r,
+        "s" => SDLK_s,
+        "t" => SDLK_t,
+        "u" => SDLK_u,
+        "v" => SDLK_v,
+        "w" => SDLK_w,
+        "x" => SDLK_x,
+        "y" => SDLK_y,
+        "z" => SDLK_z,
+        "0" => SDLK_0,
+        "1" => SDLK_1,
+        "2" => SDLK_2,
+        "3" => SDLK_3,
+        "4" => SDLK_4,
+        "5" => SDLK_5,
+        "6" => SDLK_6,
+        "7" => SDLK_7,
+        "8" => SDLK_8,
+        "9" => SDLK_9,
+        "up" => SDLK_UP,
+        "down" => SDLK_DOWN,
+        "left" => SDLK_LEFT,
+        "right" => SDLK_RIGHT,
+        "space" => SDLK_SPACE,
+        "tab" => SDLK_TAB,
+        "enter" => SDLK_RETURN,
+        "escape" => SDLK_ESCAPE,
+        _ => return None,
+    })
+}
+
+// Lines of `action = key, key, ...`, actions may be left unbound
+pub fn parse_bindings(text: &str, file: &str) -> Result<Vec<(SDL_KeyCode, Action)>, Vec<String>> {
+    let mut errs = Vec::new();
+    let mut err = |line: usize, msg: String| errs.push(format!("{file}:{line}: {msg}"));
+
+    let mut bindings = Vec::new();
+    let mut seen = Vec::new();
+    for (i, line) in text.lines().enumerate().map(|(i, l)| (i + 1, l.trim())) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (name, keys) = match line.split_once('=') {
+            Some((k, v)) => (k.trim(), v.trim()),
+            None => {
+                err(i, format!("Expected 'action = key', got '{line}'"));
+                continue;
+            }
+        };
+        let action = match Action::from_name(name) {
+            Some(action) => action,
+            None => {
+                err(i, format!("Unknown action '{name}'"));
+                continue;
+            }
+        };
+        if seen.contains(&action) {
+            err(i, format!("Duplicate action '{name}'"));
+        }
+        seen.push(action);
+
+        for key in keys.split(',').map(str::trim).filter(|k| !k.is_empty()) {
+            match key_from_name(&key.to_lowercase()) {
+                Some(code) => bindings.push((code, action)),
+                None => err(i, format!("Unknown key '{key}'")),
+            }
+        }
+    }
+
+    match errs.is_empty() {
+        true => Ok(bindings),
+        false => Err(errs),
+    }
+}
+
+pub fn load_bindings() -> Vec<(SDL_KeyCode, Action)> {
+    let res = fs::read_to_string(CONTROLS_FILE)
+        .map_err(|e| vec![format!("{CONTROLS_FILE}: {e}")])
+        .and_then(|text| parse_bindings(&text, CONTROLS_FILE));
+    match res {
+        Ok(bindings) => bindings,
+        Err(errs) => panic!("Invalid key bindings:\n{}", errs.join("\n")),
+    }
+}
+
+#[hyperfold_engine::global]
+struct Input {
+    // A key may trigger several actions and an action may have several keys
+    bindings: Vec<(SDL_KeyCode, Action)>,
+    // Keys currently down, in press order
+    held: Vec<SDL_KeyCode>,
+}
+
+impl Input {
+    pub fn new() -> Self {
+        Self {
+            bindings: load_bindings(),
+            held: Vec::new(),
+        }
+    }
+
+    pub fn is_held(&self, action: Action) -> bool {
+        (self.bindings.iter()).any(|(k, a)| *a == action && self.held.contains(k))
+    }
+
+    // -1, 0 or 1 depending on which of the two actions are held
+    pub fn axis(&self, neg: Action, pos: Action) -> f32 {
+        self.is_held(pos) as u8 as f32 - self.is_held(neg) as u8 as f32
+    }
+}
+
+// Sent once when a key bound to the action goes down
+#[hyperfold_engine::event]
+struct ActionPressed(pub Action);
+
+// Held state is replaced rather than accumulated, so repeated key downs are ignored
+#[hyperfold_engine::system]
+fn map_keys(ev: &Key, input: &mut Input, events: &mut dyn Events) {
+    let key = ev.0.key;
+    if ev.0.up() {
+        input.held.retain(|k| *k != key);
+    }
+    if !ev.0.down() || input.held.contains(&key) {
+        return;
+    }
+
+    input.held.push(key);
+    for (_, action) in input.bindings.iter().filter(|(k, _)| *k == key) {
+        events.new_event(ActionPressed(*action));
+    }
+}
+
+// Key releases are missed while the window is unfocused, so nothing stays held
+#[hyperfold_engine::system]
+fn release_keys(_: &FocusLost, input: &mut Input) {
+    input.held.clear();
+}
+
+// While paused, enemies, fireballs, spells and the wizards stop
+#[hyperfold_engine::global]
+struct Paused(pub bool);
+
+impl Paused {
+    pub fn new() -> Self {
+        Self(false)
+    }
+}
+
+#[hyperfold_engine::system]
+fn toggle_pause(ActionPressed(action): &ActionPressed, paused: &mut Paused) {
+    if *action == Action::Pause {
+        paused.0 = !paused.0;
+    }
+}
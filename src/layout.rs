@@ -0,0 +1,36 @@
+use hyperfold_engine::{ecs::events::core::Update, framework::render_system::Screen};
+
+use crate::_engine::Events;
+
+// Screen size the UI was last laid out for
+#[hyperfold_engine::global]
+struct LayoutSize {
+    pub w: u32,
+    pub h: u32,
+}
+
+impl LayoutSize {
+    pub fn new() -> Self {
+        Self { w: 0, h: 0 }
+    }
+}
+
+// Sent when the screen size changes, absolute UI should recompute its rects
+#[hyperfold_engine::event]
+struct ScreenResized {
+    pub w: f32,
+    pub h: f32,
+}
+
+#[hyperfold_engine::system]
+fn detect_resize(_: &Update, screen: &Screen, size: &mut LayoutSize, events: &mut dyn Events) {
+    let (w, h) = (screen.0.w, screen.0.h);
+    if (w, h) != (size.w, size.h) {
+        size.w = w;
+        size.h = h;
+        events.new_event(ScreenResized {
+            w: w as f32,
+            h: h as f32,
+        });
+    }
+}
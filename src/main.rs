@@ -3,11 +3,30 @@
 #![feature(trait_alias)]
 #![feature(array_methods)]
 
+mod archetypes;
+mod autobuy;
+mod carousel;
+mod catalyst;
 mod crystal;
+mod effects;
 mod enemies;
 mod fireball;
+mod health;
+mod hiring;
+mod input;
+mod layout;
+mod offline;
 pub mod param_dag;
+mod research;
+mod respec;
+mod save;
+mod spells;
+mod stats;
+mod targeting;
 mod text_wizard;
+mod unlocks;
+mod upgrade_defs;
+mod upgrade_tooltip;
 mod upgrades;
 mod utils;
 mod wizard;
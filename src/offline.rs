@@ -0,0 +1,179 @@
+use hyperfold_engine::{
+    add_components, components,
+    ecs::{
+        entities::{Entity, EntityTrash, NewEntity},
+        events::core::Update,
+    },
+    framework::{
+        physics::Position,
+        render_system::{
+            font::{FontData, TIMES},
+            render_data::Fit,
+            render_text::RenderText,
+            RenderComponent, RenderOpts, Screen,
+        },
+    },
+    utils::{
+        colors::{TRANSPARENT, WHITE},
+        number::Number,
+        rect::{Align, Rect},
+        timer::{Timer, TimerTrait},
+    },
+};
+
+use crate::{
+    _engine::Components,
+    crystal::gain_magic,
+    layout::ScreenResized,
+    param_dag::{Dag, NumDag},
+    utils::{elevations::Elevations, time::format_duration},
+    wizard::WizardNumbers,
+};
+
+#[hyperfold_engine::global]
+struct OfflineConfig {
+    // Offline time beyond this is ignored
+    pub cap_ms: u64,
+    // Fraction of normal fireball output earned while away
+    pub efficiency: f64,
+}
+
+impl OfflineConfig {
+    pub fn new() -> Self {
+        Self {
+            cap_ms: 8 * 60 * 60 * 1000,
+            efficiency: 0.5,
+        }
+    }
+}
+
+// Awards progress for time spent with the game closed
+// Sent when the save is loaded
+#[hyperfold_engine::event]
+struct OfflineProgress {
+    pub elapsed_ms: u64,
+}
+
+// Simulates `elapsed_ms` of income from every wizard
+// Power depends on magic, so the duration is split into a bounded number of steps
+// rather than simulating every attack
+pub fn simulate_offline(dag: &mut Dag<Number>, elapsed_ms: u64, efficiency: f64) -> Number {
+    const MAX_STEPS: u64 = 100;
+    const MIN_STEP_MS: u64 = 1000;
+
+    let steps = (elapsed_ms / MIN_STEP_MS).min(MAX_STEPS);
+    let mut gained = Number::from(0);
+    for i in 0..steps {
+        // Spread the remainder over the first steps
+        let ms = elapsed_ms / steps + (i < elapsed_ms % steps) as u64;
+        // Income changes with magic and anything loaded from the save
+        dag.propagate();
+        let gain = *dag.get(WizardNumbers::Income) * Number::from(ms as f64 / 1000.0 * efficiency);
+        gain_magic(dag, gain);
+        gained = gained + gain;
+    }
+    gained
+}
+
+#[hyperfold_engine::system]
+fn offline_progress(
+    OfflineProgress { elapsed_ms }: &OfflineProgress,
+    dag: &mut NumDag,
+    config: &OfflineConfig,
+    entities: &mut dyn Components,
+    screen: &Screen,
+) {
+    let elapsed_ms = (*elapsed_ms).min(config.cap_ms);
+    let gained = simulate_offline(&mut dag.0, elapsed_ms, config.efficiency);
+    if gained <= 0.into() {
+        return;
+    }
+
+    spawn_offline_summary(
+        format!(
+            "While you were away ({})\n+{gained} magic",
+            format_duration(elapsed_ms)
+        ),
+        entities,
+        screen,
+    );
+}
+
+// "While you were away" summary
+#[hyperfold_engine::component]
+struct OfflineSummary;
+
+// Centered on the screen
+fn summary_rect(w: f32, h: f32) -> Rect {
+    Rect::from(
+        w / 2.0,
+        h / 2.0,
+        w / 2.0,
+        80.0,
+        Align::Center,
+        Align::Center,
+    )
+}
+
+fn spawn_offline_summary(text: String, entities: &mut dyn Components, screen: &Screen) {
+    let rect = summary_rect(screen.0.w as f32, screen.0.h as f32);
+
+    let e = Entity::new();
+    add_components!(
+        entities,
+        e,
+        OfflineSummary,
+        RenderOpts::new(Elevations::Popups as u8).absolute(),
+        RenderComponent::new(
+            RenderText::new(FontData {
+                w: None,
+                h: Some(30),
+                sample: "9.99e999".to_string(),
+                file: TIMES.to_string()
+            })
+            .with_text_align(Align::Center, Align::Center)
+            .with_text_color(WHITE)
+            .with_text(&text)
+            .with_background_color(TRANSPARENT)
+            .with_dest_fit(Fit::fit_height())
+            .with_dest_align(Align::Center, Align::Center)
+        ),
+        Position(rect),
+        Timer::new(5000),
+    );
+}
+
+components!(
+    labels(OfflineSummary),
+    OfflineSummaryTimer,
+    timer: &'a mut Timer
+);
+
+#[hyperfold_engine::system]
+fn update_offline_summary(
+    dt: &Update,
+    summaries: Vec<OfflineSummaryTimer>,
+    trash: &mut EntityTrash,
+) {
+    for OfflineSummaryTimer { eid, timer } in summaries {
+        if timer.add_time(dt.0) > 0 {
+            trash.0.push(*eid);
+        }
+    }
+}
+
+components!(
+    labels(OfflineSummary),
+    OfflineSummaryPos,
+    pos: &'a mut Position
+);
+
+#[hyperfold_engine::system]
+fn layout_offline_summary(
+    ScreenResized { w, h }: &ScreenResized,
+    summaries: Vec<OfflineSummaryPos>,
+) {
+    for OfflineSummaryPos { pos, .. } in summaries {
+        pos.0 = summary_rect(*w, *h);
+    }
+}
@@ -13,7 +13,7 @@ use hyperfold_engine::utils::{number::Number, traits::Id, util::get_time};
 use itertools::Itertools;
 
 // Node Structs
-type NodeId = u64;
+pub type NodeId = u64;
 
 #[derive(Copy, Clone, PartialEq, Eq)]
 pub enum ValueType {
@@ -42,6 +42,8 @@ struct RootValue<T> {
     id: NodeId,
     value: T,
     updated: u32,
+    // Set when the root changes in eager mode, cleared on propagation
+    dirty: bool,
 }
 
 impl<T> RootValue<T> {
@@ -50,6 +52,7 @@ impl<T> RootValue<T> {
             id,
             value,
             updated: get_time(),
+            dirty: false,
         }
     }
 }
@@ -65,6 +68,11 @@ struct UpdateData<T, const N: usize, F: UpdateFunc<T, N>> {
 trait Update<T> {
     fn update(&self, dag: &Dag<T>, updated: u32) -> Option<T>;
 
+    fn compute(&self, dag: &Dag<T>) -> T;
+
+    // Args are in the same order as get_values()
+    fn compute_from(&self, args: &[&T]) -> T;
+
     fn get_values(&self) -> Iter<ValueType>;
 
     fn get_values_desc(&self) -> Rev<IntoIter<ValueType>> {
@@ -94,6 +102,63 @@ impl<T, const N: usize, F: UpdateFunc<T, N>> Update<T> for UpdateData<T, N, F> {
         update.then(|| (self.func)(args))
     }
 
+    fn compute(&self, dag: &Dag<T>) -> T {
+        (self.func)(self.values.clone().map(|i| match i {
+            ValueType::Root(i) => &dag.roots[i].value,
+            ValueType::Node(i) => &dag.nodes[i].value,
+        }))
+    }
+
+    fn compute_from(&self, args: &[&T]) -> T {
+        (self.func)(std::array::from_fn(|i| args[i]))
+    }
+
+    fn get_values(&self) -> Iter<ValueType> {
+        self.values.iter()
+    }
+
+    fn update_nodes(&mut self, idxs: &Vec<usize>) {
+        for i in &mut self.values {
+            match i {
+                ValueType::Root(_) => (),
+                ValueType::Node(i) => *i = idxs[*i],
+            }
+        }
+    }
+}
+
+// Same as UpdateData but for a number of values only known at runtime
+pub trait UpdateFuncDyn<T> = Fn(&[&T]) -> T;
+
+struct UpdateDataDyn<T, F: UpdateFuncDyn<T>> {
+    values: Vec<ValueType>,
+    func: F,
+    pd: PhantomData<T>,
+}
+
+impl<T, F: UpdateFuncDyn<T>> Update<T> for UpdateDataDyn<T, F> {
+    fn update(&self, dag: &Dag<T>, updated: u32) -> Option<T> {
+        let update = self.values.iter().any(|i| match i {
+            ValueType::Root(i) => dag.roots[*i].updated > updated,
+            ValueType::Node(i) => dag.nodes[*i].updated > updated,
+        });
+        update.then(|| self.compute(dag))
+    }
+
+    fn compute(&self, dag: &Dag<T>) -> T {
+        let args: Vec<_> = (self.values.iter())
+            .map(|i| match i {
+                ValueType::Root(i) => &dag.roots[*i].value,
+                ValueType::Node(i) => &dag.nodes[*i].value,
+            })
+            .collect();
+        (self.func)(&args)
+    }
+
+    fn compute_from(&self, args: &[&T]) -> T {
+        (self.func)(args)
+    }
+
     fn get_values(&self) -> Iter<ValueType> {
         self.values.iter()
     }
@@ -114,6 +179,8 @@ struct NodeValue<T> {
     value: T,
     update: Box<dyn Update<T>>,
     updated: u32,
+    // Set when the node needs recomputing in eager mode
+    dirty: bool,
 }
 
 impl<T: Default + 'static> NodeValue<T> {
@@ -129,6 +196,7 @@ impl<T: Default + 'static> NodeValue<T> {
                 pd: PhantomData,
             }),
             updated: 0,
+            dirty: false,
         }
     }
 }
@@ -179,6 +247,11 @@ impl<T, U: Root<T>> NodeDefault<T, RootMarker> for U {
 pub struct Dag<T> {
     roots: Vec<RootValue<T>>,
     nodes: Vec<NodeValue<T>>,
+    // Eager: root changes are pushed to dependent nodes by propagate()
+    // Lazy: nodes are recomputed when they are read
+    eager: bool,
+    // Some root or node is dirty, so reads in eager mode propagate first
+    pending: bool,
 }
 
 impl<T> Dag<T> {
@@ -186,9 +259,35 @@ impl<T> Dag<T> {
         Self {
             roots: Vec::new(),
             nodes: Vec::new(),
+            eager: false,
+            pending: false,
         }
     }
 
+    pub fn new_eager() -> Self {
+        Self {
+            eager: true,
+            ..Self::new()
+        }
+    }
+
+    pub fn is_eager(&self) -> bool {
+        self.eager
+    }
+
+    pub fn set_eager(&mut self, eager: bool) {
+        // Pending changes would be lost when switching to lazy
+        if self.eager && !eager {
+            self.propagate();
+        }
+        // Values cached while lazy may be stale
+        if !self.eager && eager {
+            self.nodes.iter_mut().for_each(|n| n.dirty = true);
+            self.pending = true;
+        }
+        self.eager = eager;
+    }
+
     fn find_node(&self, id: NodeId) -> Option<(usize, &NodeValue<T>)> {
         self.nodes.iter().enumerate().find(|(_, n)| n.id == id)
     }
@@ -198,8 +297,16 @@ impl<T> Dag<T> {
     }
 
     fn get_node_impl(&mut self, id: NodeId) -> &NodeValue<T> {
-        // Collect all indices in descending order
+        // Values are kept up to date by propagate(), which also runs early if
+        // something changed since the last one so reads are never stale
+        if self.eager {
+            self.propagate();
+            let (idx, _) = self.find_node(id).expect("Node does not exist");
+            return &self.nodes[idx];
+        }
         let (idx, _) = self.find_node(id).expect("Node does not exist");
+
+        // Collect all indices in descending order
         let mut idxs: VecDeque<_> = once(idx).collect();
         let mut i = 0;
         while i < idxs.len() {
@@ -236,17 +343,17 @@ impl<T> Dag<T> {
         let idx = match self.roots.iter().position(|r| r.id == id) {
             Some(i) => i,
             None => {
-                self.roots.push(RootValue {
-                    id,
-                    value: default,
-                    updated: get_time(),
-                });
+                self.roots.push(RootValue::new(id, default));
                 self.roots.len() - 1
             }
         };
         &self.roots[idx]
     }
 
+    pub fn get_node_by_id(&mut self, id: NodeId) -> &T {
+        &self.get_node_impl(id).value
+    }
+
     pub fn get<U>(&mut self, n: impl NodeDefault<T, U>) -> &T {
         match n.default() {
             Some(t) => &self.get_root_impl(n.id(), t).value,
@@ -255,16 +362,26 @@ impl<T> Dag<T> {
     }
 
     pub fn set(&mut self, r: impl Root<T>, value: T) {
-        let id = r.id();
+        self.set_root_by_id(r.id(), value)
+    }
+
+    // Used when the root type isn't known statically, e.g. when loading saves
+    pub fn get_root_by_id(&mut self, id: NodeId, default: T) -> &T {
+        &self.get_root_impl(id, default).value
+    }
+
+    pub fn set_root_by_id(&mut self, id: NodeId, value: T) {
+        let eager = self.eager;
+        self.pending |= eager;
         match self.roots.iter_mut().find(|r| r.id == id) {
             Some(r) => {
                 r.value = value;
                 r.updated = get_time();
+                r.dirty = eager;
             }
             None => self.roots.push(RootValue {
-                id,
-                value,
-                updated: get_time(),
+                dirty: eager,
+                ..RootValue::new(id, value)
             }),
         }
     }
@@ -278,6 +395,36 @@ impl<T> Dag<T> {
         let value = r.default();
         self.set(r, value)
     }
+
+    // Recomputes every node downstream of a changed root, in depth order
+    // Each node is computed at most once
+    pub fn propagate(&mut self) {
+        if !self.pending {
+            return;
+        }
+        self.pending = false;
+
+        let t = get_time();
+        for i in 0..self.nodes.len() {
+            // Nodes are sorted by depth so dependencies are already up to date
+            let node = &self.nodes[i];
+            let dirty = node.dirty
+                || node.update.get_values().any(|v| match v {
+                    ValueType::Root(i) => self.roots[*i].dirty,
+                    ValueType::Node(i) => self.nodes[*i].dirty,
+                });
+            if dirty {
+                let value = node.update.compute(self);
+                let node = &mut self.nodes[i];
+                node.value = value;
+                node.updated = t;
+                node.dirty = true;
+            }
+        }
+
+        self.roots.iter_mut().for_each(|r| r.dirty = false);
+        self.nodes.iter_mut().for_each(|n| n.dirty = false);
+    }
 }
 
 impl<T: Default + 'static> Dag<T> {
@@ -286,7 +433,39 @@ impl<T: Default + 'static> Dag<T> {
         n: impl Node<T>,
         (values, func): ([(NodeId, Option<T>); N], impl UpdateFunc<T, N> + 'static),
     ) {
-        let values = values.map(|(id, def)| match def {
+        let values = values.map(|v| self.resolve_value(v));
+        self.insert_node(
+            n.id(),
+            Box::new(UpdateData {
+                values,
+                func,
+                pd: PhantomData,
+            }),
+        )
+    }
+
+    // Same as add_node() but with any number of values
+    // Values are passed to func in the same order
+    pub fn add_node_dyn(
+        &mut self,
+        n: impl Node<T>,
+        values: Vec<(NodeId, Option<T>)>,
+        func: impl UpdateFuncDyn<T> + 'static,
+    ) {
+        let values = values.into_iter().map(|v| self.resolve_value(v)).collect();
+        self.insert_node(
+            n.id(),
+            Box::new(UpdateDataDyn {
+                values,
+                func,
+                pd: PhantomData,
+            }),
+        )
+    }
+
+    // Finds or creates the root/node for a value
+    fn resolve_value(&mut self, (id, def): (NodeId, Option<T>)) -> ValueType {
+        match def {
             Some(t) => ValueType::Root(match self.find_root(id) {
                 Some((i, _)) => i,
                 None => {
@@ -301,23 +480,21 @@ impl<T: Default + 'static> Dag<T> {
                     self.nodes.len() - 1
                 }
             }),
-        });
+        }
+    }
 
-        // Insert/update the node
-        let id = n.id();
-        let depth = values.iter().fold(0, |m, idx| match idx {
+    fn insert_node(&mut self, id: NodeId, update: Box<dyn Update<T>>) {
+        // A node is always deeper than its dependencies
+        let depth = update.get_values().fold(0, |m, idx| match idx {
             ValueType::Root(_) => m,
-            ValueType::Node(i) => m.max(self.nodes[*i].depth),
-        });
-        let update = Box::new(UpdateData {
-            values,
-            func,
-            pd: PhantomData,
+            ValueType::Node(i) => m.max(self.nodes[*i].depth + 1),
         });
+        self.pending = true;
         let prev_depth = match self.nodes.iter_mut().find(|n| n.id == id) {
             Some(n) => {
                 n.update = update;
                 n.updated = 0;
+                n.dirty = true;
                 std::mem::replace(&mut n.depth, depth)
             }
             None => {
@@ -327,6 +504,7 @@ impl<T: Default + 'static> Dag<T> {
                     value: Default::default(),
                     update,
                     updated: 0,
+                    dirty: true,
                 });
                 0
             }
@@ -343,7 +521,7 @@ impl<T: Default + 'static> Dag<T> {
             let nodes = n.update.get_values();
             let depth = nodes.fold(0, |m, idx| match idx {
                 ValueType::Root(_) => m,
-                ValueType::Node(i) => m.max(depths[*i]),
+                ValueType::Node(i) => m.max(depths[*i] + 1),
             });
             if depth != n.depth {
                 n.depth = depth;
@@ -364,6 +542,52 @@ impl<T: Default + 'static> Dag<T> {
     }
 }
 
+impl<T: Clone> Dag<T> {
+    // Computes a node as if a root had a different value, without modifying the dag
+    pub fn predict_by_id(&mut self, node: NodeId, root: NodeId, value: T) -> T {
+        self.predict_many_by_id(node, vec![(root, value)])
+    }
+
+    // Same as predict_by_id() with several roots changed at once
+    pub fn predict_many_by_id(&mut self, node: NodeId, roots: Vec<(NodeId, T)>) -> T {
+        // Make sure the current values are up to date
+        self.get_node_impl(node);
+        let (idx, _) = self.find_node(node).expect("Node does not exist");
+        let roots: Vec<_> = (roots.into_iter())
+            .filter_map(|(id, v)| self.find_root(id).map(|(i, _)| (i, v)))
+            .collect();
+        let new_root = |r: usize| roots.iter().find(|(i, _)| *i == r).map(|(_, v)| v);
+
+        // Dependencies always come before the node
+        let mut changed: Vec<Option<T>> = vec![None; idx + 1];
+        for i in 0..=idx {
+            let n = &self.nodes[i];
+            let affected = n.update.get_values().any(|v| match v {
+                ValueType::Root(r) => new_root(*r).is_some(),
+                ValueType::Node(j) => changed[*j].is_some(),
+            });
+            if affected {
+                let args: Vec<_> = (n.update.get_values())
+                    .map(|v| match v {
+                        ValueType::Root(r) => new_root(*r).unwrap_or(&self.roots[*r].value),
+                        ValueType::Node(j) => changed[*j].as_ref().unwrap_or(&self.nodes[*j].value),
+                    })
+                    .collect();
+                let new_value = n.update.compute_from(&args);
+                changed[i] = Some(new_value);
+            }
+        }
+
+        changed[idx]
+            .take()
+            .unwrap_or_else(|| self.nodes[idx].value.clone())
+    }
+
+    pub fn predict(&mut self, n: impl Node<T>, r: impl Root<T>, value: T) -> T {
+        self.predict_by_id(n.id(), r.id(), value)
+    }
+}
+
 #[macro_export]
 macro_rules! equation {
     (|($($var: ident: $ty: expr,)*)| $body: expr) => {
@@ -376,10 +600,16 @@ struct NumDag(pub Dag<Number>);
 
 impl NumDag {
     pub fn new() -> Self {
-        Self(Dag::new())
+        Self(Dag::new_eager())
     }
 }
 
+// Pushes root changes through the dag once per frame
+#[hyperfold_engine::system]
+fn propagate_num_dag(_: &hyperfold_engine::ecs::events::core::Update, dag: &mut NumDag) {
+    dag.0.propagate();
+}
+
 #[macro_export]
 macro_rules! parameters {
     (@def $name: ident) => {
@@ -0,0 +1,293 @@
+use hyperfold_engine::{
+    add_components, components,
+    ecs::{
+        entities::{Entity, EntityTrash, NewEntity},
+        events::core::Update,
+    },
+    framework::{
+        physics::Position,
+        render_system::{
+            render_data::RenderTexture, RenderComponent, RenderOpts, Renderer, Texture,
+        },
+    },
+    sdl2::SDL_Color,
+    utils::rect::{Align, Rect},
+};
+
+use crate::{
+    _engine::Components,
+    catalyst::Prestige,
+    offline::{OfflineConfig, OfflineProgress},
+    param_dag::NumDag,
+    upgrades::{Upgrade, UpgradeBoxData, Upgrades},
+    utils::elevations::Elevations,
+};
+
+const FILL_COLOR: SDL_Color = SDL_Color {
+    r: 64,
+    g: 160,
+    b: 255,
+    a: 128,
+};
+
+// One level of a research upgrade, applied once it completes
+#[derive(Clone)]
+pub struct ResearchJob {
+    // Upgrade key from the definitions file
+    pub key: String,
+    pub duration_ms: u64,
+    pub progress_ms: u64,
+}
+
+impl ResearchJob {
+    pub fn left_ms(&self) -> u64 {
+        self.duration_ms.saturating_sub(self.progress_ms)
+    }
+
+    pub fn frac(&self) -> f32 {
+        self.progress_ms as f32 / self.duration_ms.max(1) as f32
+    }
+
+    // key:duration_ms:progress_ms
+    pub fn encode(&self) -> String {
+        format!("{}:{}:{}", self.key, self.duration_ms, self.progress_ms)
+    }
+
+    pub fn decode(s: &str) -> Result<Self, String> {
+        let parts = s.split(':').collect::<Vec<_>>();
+        let [key, duration, progress] = parts[..] else {
+            return Err(format!("Expected 'key:duration:progress', got '{s}'"));
+        };
+        Ok(Self {
+            key: key.to_string(),
+            duration_ms: (duration.parse())
+                .map_err(|_| format!("Invalid duration '{duration}'"))?,
+            progress_ms: (progress.parse())
+                .map_err(|_| format!("Invalid progress '{progress}'"))?,
+        })
+    }
+}
+
+pub enum ResearchStatus {
+    Running { left_ms: u64 },
+    // Position in the queue, starting at 1
+    Queued(usize),
+}
+
+// The first `slots` jobs are running, the rest wait their turn
+#[hyperfold_engine::global]
+struct ResearchQueue {
+    pub slots: usize,
+    jobs: Vec<ResearchJob>,
+}
+
+impl ResearchQueue {
+    pub fn new() -> Self {
+        Self {
+            slots: 2,
+            jobs: Vec::new(),
+        }
+    }
+
+    pub fn jobs(&self) -> &Vec<ResearchJob> {
+        &self.jobs
+    }
+
+    pub fn set_jobs(&mut self, jobs: Vec<ResearchJob>) {
+        self.jobs = jobs;
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.jobs.iter().any(|j| j.key == key)
+    }
+
+    // Drops the job without completing it
+    pub fn cancel(&mut self, key: &str) {
+        self.jobs.retain(|j| j.key != key);
+    }
+
+    pub fn push(&mut self, key: &str, duration_ms: u64) {
+        self.jobs.push(ResearchJob {
+            key: key.to_string(),
+            duration_ms,
+            progress_ms: 0,
+        });
+    }
+
+    fn running(&self) -> &[ResearchJob] {
+        &self.jobs[..self.slots.min(self.jobs.len())]
+    }
+
+    pub fn status(&self, key: &str) -> Option<ResearchStatus> {
+        let i = self.jobs.iter().position(|j| j.key == key)?;
+        Some(match i < self.slots {
+            true => ResearchStatus::Running {
+                left_ms: self.jobs[i].left_ms(),
+            },
+            false => ResearchStatus::Queued(i + 1 - self.slots),
+        })
+    }
+
+    // Runs for the given time, starting queued jobs as slots free up
+    // Calls complete() for each finished job in order
+    pub fn advance(&mut self, mut ms: u64, mut complete: impl FnMut(&ResearchJob)) {
+        loop {
+            let n = self.running().len();
+            if n == 0 {
+                break;
+            }
+
+            // Step to the next completion or the end of the time
+            let step = (self.running().iter())
+                .map(|j| j.left_ms())
+                .min()
+                .unwrap_or(0)
+                .min(ms);
+            for job in &mut self.jobs[..n] {
+                job.progress_ms += step;
+            }
+            ms -= step;
+
+            let (done, left): (Vec<_>, Vec<_>) =
+                self.jobs.drain(..).partition(|j| j.left_ms() == 0);
+            self.jobs = left;
+            done.iter().for_each(&mut complete);
+            if done.is_empty() {
+                break;
+            }
+        }
+    }
+}
+
+// Completed research applies its level through the Dag like a normal purchase
+fn complete_research(
+    queue: &mut ResearchQueue,
+    ms: u64,
+    upgrades: &[Upgrades],
+    dag: &mut NumDag,
+) -> bool {
+    let mut completed = false;
+    queue.advance(ms, |job| {
+        match upgrades.iter().find(|u| u.up.key == job.key) {
+            Some(Upgrades { up, .. }) => dag.0.update(up.level, |l| *l + 1.into()),
+            None => eprintln!("Unknown research upgrade: {}", job.key),
+        }
+        completed = true;
+    });
+    completed
+}
+
+#[hyperfold_engine::system]
+fn update_research(
+    Update(dt): &Update,
+    upgrades: Vec<Upgrades>,
+    UpgradeBoxData { up_box, .. }: UpgradeBoxData,
+    queue: &mut ResearchQueue,
+    dag: &mut NumDag,
+) {
+    if complete_research(queue, *dt as u64, &upgrades, dag) {
+        up_box.update = true;
+    }
+}
+
+// Research keeps running while the game is closed
+#[hyperfold_engine::system]
+fn research_offline(
+    OfflineProgress { elapsed_ms }: &OfflineProgress,
+    upgrades: Vec<Upgrades>,
+    queue: &mut ResearchQueue,
+    config: &OfflineConfig,
+    dag: &mut NumDag,
+) {
+    complete_research(queue, (*elapsed_ms).min(config.cap_ms), &upgrades, dag);
+}
+
+#[hyperfold_engine::system]
+fn reset_research(_: &Prestige, queue: &mut ResearchQueue) {
+    queue.jobs.clear();
+}
+
+// Progress fill over the icon of running research
+#[hyperfold_engine::component]
+struct ResearchFill {
+    key: String,
+}
+
+components!(
+    ResearchFills,
+    fill: &'a ResearchFill,
+    pos: &'a mut Position,
+    opts: &'a mut RenderOpts
+);
+components!(
+    ResearchIcons,
+    up: &'a Upgrade,
+    pos: &'a Position,
+    opts: &'a RenderOpts
+);
+
+#[hyperfold_engine::system]
+fn update_research_fills(
+    _: &Update,
+    icons: Vec<ResearchIcons>,
+    fills: Vec<ResearchFills>,
+    queue: &ResearchQueue,
+    entities: &mut dyn Components,
+    trash: &mut EntityTrash,
+    r: &Renderer,
+) {
+    // Fills of finished research are removed
+    let mut fills = fills
+        .into_iter()
+        .filter(
+            |f| match queue.running().iter().any(|j| j.key == f.fill.key) {
+                true => true,
+                false => {
+                    trash.0.push(*f.eid);
+                    false
+                }
+            },
+        )
+        .collect::<Vec<_>>();
+
+    for job in queue.running() {
+        let icon = match icons.iter().find(|i| i.up.key == job.key) {
+            Some(icon) => icon,
+            None => continue,
+        };
+        let fill = match fills.iter_mut().find(|f| f.fill.key == job.key) {
+            Some(fill) => fill,
+            None => {
+                let e = Entity::new();
+                add_components!(
+                    entities,
+                    e,
+                    ResearchFill {
+                        key: job.key.clone()
+                    },
+                    Position(Rect::new()),
+                    RenderOpts::new(Elevations::UpgradeBadges as u8)
+                        .absolute()
+                        .is_visible(false),
+                    RenderComponent::new(RenderTexture::new(Some(Texture::new(
+                        r, 1, 1, FILL_COLOR
+                    )))),
+                );
+                continue;
+            }
+        };
+
+        // Fills from the bottom up
+        let rect = &icon.pos.0;
+        let h = rect.h * job.frac();
+        fill.pos.0 = Rect::from(
+            rect.x,
+            rect.y + rect.h - h,
+            rect.w,
+            h,
+            Align::TopLeft,
+            Align::TopLeft,
+        );
+        fill.opts.set_visible(icon.opts.visible);
+    }
+}
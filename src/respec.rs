@@ -0,0 +1,245 @@
+use std::any::TypeId;
+
+use hyperfold_engine::{
+    add_components, components,
+    ecs::{
+        entities::{Entity, NewEntity},
+        events::core::Update,
+    },
+    framework::{
+        event_system::events::Click,
+        physics::Position,
+        render_system::{
+            font::{FontData, TIMES},
+            render_data::Fit,
+            render_text::RenderText,
+            RenderComponent, RenderOpts,
+        },
+    },
+    utils::{
+        colors::{GRAY, GREEN, WHITE},
+        number::Number,
+        rect::{Align, PointF, Rect},
+        timer::{Timer, TimerTrait},
+        util::AsType,
+    },
+};
+
+use crate::{
+    _engine::{Components, Events},
+    crystal::{CrystalNumbers, CrystalTextPos},
+    effects::spawn_floating_text,
+    param_dag::{Dag, NumDag},
+    research::ResearchQueue,
+    upgrades::{UpgradeBoxData, UpgradeBoxPos, Upgrades},
+    utils::{
+        elevations::Elevations,
+        time::{format_duration, now_ms},
+    },
+};
+
+// How long the confirm prompt stays up
+const CONFIRM_MS: u32 = 3000;
+
+#[hyperfold_engine::global]
+struct RespecConfig {
+    // Fraction of the magic spent on the group that is given back
+    pub refund: f64,
+    // Time between respecs of any group
+    pub cooldown_ms: u32,
+}
+
+impl RespecConfig {
+    pub fn new() -> Self {
+        Self {
+            refund: 0.75,
+            cooldown_ms: 5 * 60 * 1000,
+        }
+    }
+}
+
+// Resets every upgrade in the group and refunds part of what was spent on them
+#[hyperfold_engine::event]
+struct Respec(pub TypeId);
+
+// Magic refunded by respeccing the group now
+fn refund(
+    upgrades: &[Upgrades],
+    group: TypeId,
+    config: &RespecConfig,
+    dag: &mut Dag<Number>,
+) -> Number {
+    let spent = (upgrades.iter())
+        .filter(|u| u.up.group() == group)
+        .fold(Number::from(0), |s, u| s + *dag.get(u.up.spent));
+    spent * Number::from(config.refund)
+}
+
+#[hyperfold_engine::system]
+fn respec(
+    Respec(group): &Respec,
+    upgrades: Vec<Upgrades>,
+    UpgradeBoxData { up_box, .. }: UpgradeBoxData,
+    CrystalTextPos { pos: text_pos, .. }: CrystalTextPos,
+    research: &mut ResearchQueue,
+    config: &RespecConfig,
+    entities: &mut dyn Components,
+    dag: &mut NumDag,
+) {
+    let amount = refund(&upgrades, *group, config, &mut dag.0);
+    for Upgrades { up, .. } in upgrades.iter().filter(|u| u.up.group() == *group) {
+        dag.0.set(up.level, 0.into());
+        dag.0.set(up.spent, 0.into());
+        // Research was already paid for, so it is refunded with the rest
+        research.cancel(&up.key);
+    }
+    // Refunds don't count towards lifetime magic
+    dag.0.update(CrystalNumbers::Magic, |m| *m + amount);
+    // Recompute every stat the group affected at once
+    dag.0.propagate();
+    up_box.update = true;
+
+    let p = text_pos.0.center();
+    spawn_floating_text(
+        entities,
+        &format!("+{amount}"),
+        PointF {
+            x: p.x,
+            y: p.y - text_pos.0.h,
+        },
+        GREEN,
+        1000,
+    );
+}
+
+// Button under the upgrade box, clicking twice respecs the open group
+#[hyperfold_engine::component(Singleton)]
+struct RespecButton {
+    confirming: bool,
+    // Real time when the next respec is allowed, so the cooldown survives restarts
+    pub ready_at: u64,
+    text: String,
+}
+
+impl RespecButton {
+    pub fn cooldown_ms(&self) -> u64 {
+        self.ready_at.saturating_sub(now_ms())
+    }
+}
+
+#[hyperfold_engine::system(Init)]
+fn init_respec(entities: &mut dyn Components) {
+    let e = Entity::new();
+    add_components!(
+        entities,
+        e,
+        RespecButton {
+            confirming: false,
+            ready_at: 0,
+            text: String::new()
+        },
+        Timer::new(CONFIRM_MS),
+        Position(Rect::new()),
+        RenderOpts::new(Elevations::UpgradeBadges as u8)
+            .absolute()
+            .is_visible(false),
+        RenderComponent::new(
+            RenderText::new(FontData {
+                w: None,
+                h: Some(20),
+                sample: "Refund 9.99e999?".to_string(),
+                file: TIMES.to_string()
+            })
+            .with_text_color(WHITE)
+            .with_background_color(GRAY)
+            .with_dest_fit(Fit::fit_height())
+            .with_dest_align(Align::BotRight, Align::TopLeft)
+        )
+    );
+}
+
+components!(
+    RespecButtonData,
+    button: &'a mut RespecButton,
+    timer: &'a mut Timer,
+    pos: &'a mut Position,
+    opts: &'a mut RenderOpts,
+    text: &'a mut RenderComponent
+);
+
+#[hyperfold_engine::system]
+fn click_respec(
+    m: &Click,
+    RespecButtonData {
+        eid, button, timer, ..
+    }: RespecButtonData,
+    UpgradeBoxPos { up_box, .. }: UpgradeBoxPos,
+    upgrades: Vec<Upgrades>,
+    config: &RespecConfig,
+    events: &mut dyn Events,
+    dag: &mut NumDag,
+) {
+    let group = match up_box.open_id() {
+        Some(group) if m.is_me(eid) && button.cooldown_ms() == 0 => group,
+        _ => return,
+    };
+
+    if button.confirming {
+        events.new_event(Respec(group));
+        button.confirming = false;
+        button.ready_at = now_ms() + config.cooldown_ms as u64;
+    } else if refund(&upgrades, group, config, &mut dag.0) > 0.into() {
+        button.confirming = true;
+        *timer = Timer::new(CONFIRM_MS);
+    }
+}
+
+#[hyperfold_engine::system]
+fn update_respec_button(
+    Update(dt): &Update,
+    RespecButtonData {
+        button,
+        timer,
+        pos,
+        opts,
+        text,
+        ..
+    }: RespecButtonData,
+    UpgradeBoxPos {
+        up_box,
+        pos: box_pos,
+        ..
+    }: UpgradeBoxPos,
+    upgrades: Vec<Upgrades>,
+    config: &RespecConfig,
+    dag: &mut NumDag,
+) {
+    if button.confirming && timer.add_time(*dt) > 0 {
+        button.confirming = false;
+    }
+
+    // Right below the box, opposite the buy mode button
+    opts.set_visible(up_box.is_open());
+    pos.0 = Rect::from(
+        box_pos.0.x + box_pos.0.w,
+        box_pos.0.y + box_pos.0.h,
+        120.0,
+        25.0,
+        Align::BotRight,
+        Align::TopLeft,
+    );
+
+    let group = match up_box.open_id() {
+        Some(group) => group,
+        None => return,
+    };
+    let new_text = match (button.cooldown_ms(), button.confirming) {
+        (0, true) => format!("Refund {}?", refund(&upgrades, group, config, &mut dag.0)),
+        (0, false) => "Respec".to_string(),
+        (ms, _) => format!("Respec {}", format_duration(ms)),
+    };
+    if new_text != button.text {
+        text.try_as_mut(|t: &mut RenderText| t.set_text(&new_text));
+        button.text = new_text;
+    }
+}
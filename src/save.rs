@@ -0,0 +1,499 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use hyperfold_engine::{
+    add_components, components,
+    ecs::{
+        entities::{Entity, NewEntity},
+        events::core::Update,
+    },
+    framework::{event_system::events::Quit, physics::Position},
+    utils::{
+        number::Number,
+        rect::{Align, PointF},
+        timer::{Timer, TimerTrait},
+    },
+};
+
+use crate::{
+    _engine::{Components, Events},
+    archetypes::{WizardCount, WizardType},
+    autobuy::{AutobuySettings, Autobuyers},
+    carousel::Carousel,
+    offline::OfflineProgress,
+    param_dag::{NodeId, NodeTrait, NumDag, Root},
+    research::{ResearchJob, ResearchQueue},
+    respec::{RespecButton, RespecButtonData},
+    spells::Spellbook,
+    unlocks::Unlocks,
+    upgrades::UpgradeBoxData,
+    utils::time::now_ms,
+    wizard::{HiredWizards, SpawnWizard, WizardPos, WizardPosMut},
+};
+
+// Bump when the format changes and add a case to migrate()
+const SAVE_VERSION: u32 = 7;
+
+pub struct SaveConfig {
+    pub file: String,
+    pub backup: String,
+    pub autosave_ms: u32,
+}
+
+impl SaveConfig {
+    pub fn new() -> Self {
+        Self {
+            file: "save/save.txt".to_string(),
+            backup: "save/save.bak".to_string(),
+            autosave_ms: 30000,
+        }
+    }
+}
+
+// Dag roots that get saved, keyed by a name that is stable between builds
+#[hyperfold_engine::global]
+struct SavedRoots(Vec<(String, NodeId, Number)>);
+
+impl SavedRoots {
+    pub fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    pub fn add(&mut self, key: &str, r: impl Root<Number>) {
+        self.0.push((key.to_string(), r.id(), r.default()));
+    }
+}
+
+#[derive(Clone)]
+pub struct SaveData {
+    // Real time when the save was written
+    pub time: u64,
+    pub roots: Vec<(String, Number)>,
+    pub wizard_pos: PointF,
+    pub upgrade_scroll: f32,
+    pub unlocked: Vec<String>,
+    // Keyed by upgrade group
+    pub autobuyers: Vec<(String, AutobuySettings)>,
+    // In queue order
+    pub research: Vec<ResearchJob>,
+    // Hired wizards and where they were placed
+    pub wizards: Vec<(WizardType, PointF)>,
+    // Real time when respeccing is allowed again
+    pub respec_ready: u64,
+    // Real time when each spell can be cast again, indexed by Spell
+    pub spell_ready: Vec<u64>,
+    // Real time when Magic Surge ends
+    pub surge_until: u64,
+}
+
+impl SaveData {
+    pub fn encode(&self) -> String {
+        let mut lines = vec![
+            format!("version={SAVE_VERSION}"),
+            format!("time={}", self.time),
+            format!("wizard_pos={},{}", self.wizard_pos.x, self.wizard_pos.y),
+            format!("upgrade_scroll={}", self.upgrade_scroll),
+            format!("unlocked={}", self.unlocked.join(",")),
+            format!(
+                "research={}",
+                self.research.iter().map(|j| j.encode()).collect::<Vec<_>>().join(",")
+            ),
+            format!(
+                "wizards={}",
+                (self.wizards.iter())
+                    .map(|(ty, p)| format!("{}:{}:{}", ty.archetype().key, p.x, p.y))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
+            format!("respec_ready={}", self.respec_ready),
+            format!(
+                "spell_ready={}",
+                (self.spell_ready.iter())
+                    .map(|t| t.to_string())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
+            format!("surge_until={}", self.surge_until),
+        ];
+        lines.extend(self.roots.iter().map(|(k, v)| format!("root.{k}={v}")));
+        lines.extend((self.autobuyers.iter()).map(|(k, a)| format!("autobuy.{k}={}", a.encode())));
+        lines.join("\n")
+    }
+
+    pub fn decode(s: &str) -> Result<Self, String> {
+        let mut fields = HashMap::new();
+        for (i, line) in s.lines().enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+            let (k, v) = line
+                .split_once('=')
+                .ok_or_else(|| format!("Line {}: expected 'key=value'", i + 1))?;
+            fields.insert(k.trim().to_string(), v.trim().to_string());
+        }
+
+        let version = parse_field::<u32>(&fields, "version")?;
+        migrate(version, &mut fields)?;
+
+        let (x, y) = get_field(&fields, "wizard_pos")?
+            .split_once(',')
+            .ok_or("wizard_pos: expected 'x,y'")?;
+        let roots = fields
+            .iter()
+            .filter_map(|(k, v)| k.strip_prefix("root.").map(|k| (k, v)))
+            .map(|(k, v)| {
+                v.parse::<Number>()
+                    .map(|v| (k.to_string(), v))
+                    .map_err(|_| format!("root.{k}: invalid number '{v}'"))
+            })
+            .collect::<Result<_, _>>()?;
+        let autobuyers = fields
+            .iter()
+            .filter_map(|(k, v)| k.strip_prefix("autobuy.").map(|k| (k, v)))
+            .map(|(k, v)| {
+                AutobuySettings::decode(v)
+                    .map(|a| (k.to_string(), a))
+                    .map_err(|e| format!("autobuy.{k}: {e}"))
+            })
+            .collect::<Result<_, _>>()?;
+        let research = get_field(&fields, "research")?
+            .split(',')
+            .filter(|s| !s.is_empty())
+            .map(|s| ResearchJob::decode(s).map_err(|e| format!("research: {e}")))
+            .collect::<Result<_, _>>()?;
+        let wizards = get_field(&fields, "wizards")?
+            .split(',')
+            .filter(|s| !s.is_empty())
+            .map(decode_wizard)
+            .collect::<Result<_, _>>()?;
+        let spell_ready = get_field(&fields, "spell_ready")?
+            .split(',')
+            .filter(|s| !s.is_empty())
+            .map(|s| (s.parse()).map_err(|_| format!("spell_ready: invalid value '{s}'")))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            time: parse_field(&fields, "time")?,
+            roots,
+            wizard_pos: PointF {
+                x: x.parse().map_err(|_| "wizard_pos: invalid x")?,
+                y: y.parse().map_err(|_| "wizard_pos: invalid y")?,
+            },
+            upgrade_scroll: parse_field(&fields, "upgrade_scroll")?,
+            unlocked: get_field(&fields, "unlocked")?
+                .split(',')
+                .filter(|s| !s.is_empty())
+                .map(|s| s.to_string())
+                .collect(),
+            autobuyers,
+            research,
+            wizards,
+            respec_ready: parse_field(&fields, "respec_ready")?,
+            spell_ready,
+            surge_until: parse_field(&fields, "surge_until")?,
+        })
+    }
+}
+
+// type:x:y
+fn decode_wizard(s: &str) -> Result<(WizardType, PointF), String> {
+    let parts = s.split(':').collect::<Vec<_>>();
+    let [ty, x, y] = parts[..] else {
+        return Err(format!("wizards: expected 'type:x:y', got '{s}'"));
+    };
+    Ok((
+        WizardType::from_key(ty).ok_or_else(|| format!("wizards: unknown type '{ty}'"))?,
+        PointF {
+            x: x.parse().map_err(|_| format!("wizards: invalid x '{x}'"))?,
+            y: y.parse().map_err(|_| format!("wizards: invalid y '{y}'"))?,
+        },
+    ))
+}
+
+fn get_field<'a>(fields: &'a HashMap<String, String>, key: &str) -> Result<&'a String, String> {
+    fields
+        .get(key)
+        .ok_or_else(|| format!("Missing field '{key}'"))
+}
+
+fn parse_field<T: std::str::FromStr>(
+    fields: &HashMap<String, String>,
+    key: &str,
+) -> Result<T, String> {
+    get_field(fields, key)?
+        .parse()
+        .map_err(|_| format!("{key}: invalid value"))
+}
+
+// Upgrades the raw fields of an old save to the current version
+fn migrate(version: u32, fields: &mut HashMap<String, String>) -> Result<(), String> {
+    match version {
+        SAVE_VERSION => Ok(()),
+        // Version 2 added autobuyers, which default to locked
+        1 => migrate(2, fields),
+        // Version 3 added the research queue
+        2 => {
+            fields.insert("research".to_string(), String::new());
+            migrate(3, fields)
+        }
+        // Version 4 added hired wizards
+        3 => {
+            fields.insert("wizards".to_string(), String::new());
+            migrate(4, fields)
+        }
+        // Version 5 added the respec cooldown
+        4 => {
+            fields.insert("respec_ready".to_string(), "0".to_string());
+            migrate(5, fields)
+        }
+        // Version 6 added spell cooldowns
+        5 => {
+            fields.insert("spell_cooldowns".to_string(), String::new());
+            fields.insert("surge_ms".to_string(), "0".to_string());
+            migrate(6, fields)
+        }
+        // Version 7 saves spell cooldowns as real times like the respec cooldown
+        // and drops the surge multiplier, which is reapplied from surge_until
+        6 => {
+            let time = parse_field::<u64>(fields, "time")?;
+            let spell_ready = get_field(fields, "spell_cooldowns")?
+                .split(',')
+                .filter(|s| !s.is_empty())
+                .map(|s| match s.parse::<u64>() {
+                    Ok(ms) => Ok((time + ms).to_string()),
+                    Err(_) => Err(format!("spell_cooldowns: invalid value '{s}'")),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let surge_until = time + parse_field::<u64>(fields, "surge_ms")?;
+            fields.insert("spell_ready".to_string(), spell_ready.join(","));
+            fields.insert("surge_until".to_string(), surge_until.to_string());
+            fields.remove("spell_cooldowns");
+            fields.remove("surge_ms");
+            fields.remove("root.surge_mult");
+            migrate(7, fields)
+        }
+        v if v > SAVE_VERSION => Err(format!("Save version {v} is newer than {SAVE_VERSION}")),
+        v => Err(format!("No migration from save version {v}")),
+    }
+}
+
+fn read_save(file: &str) -> Result<SaveData, String> {
+    SaveData::decode(&fs::read_to_string(file).map_err(|e| e.to_string())?)
+}
+
+// Loads the save, falling back to the backup if it is missing or corrupt
+pub fn load(config: &SaveConfig) -> Option<SaveData> {
+    if !Path::new(&config.file).exists() && !Path::new(&config.backup).exists() {
+        return None;
+    }
+    read_save(&config.file)
+        .or_else(|e| {
+            eprintln!("Failed to load {}: {e}", config.file);
+            read_save(&config.backup)
+        })
+        .map_err(|e| eprintln!("Failed to load {}: {e}", config.backup))
+        .ok()
+}
+
+// Writes the save, keeping the previous one as a backup
+pub fn write(config: &SaveConfig, data: &SaveData) {
+    let tmp = format!("{}.tmp", config.file);
+    let res = Path::new(&config.file)
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(&tmp, data.encode()))
+        // Copied rather than moved so the save always exists, even if we die before the rename
+        // A corrupt save never replaces the backup
+        .and_then(|_| match read_save(&config.file) {
+            Ok(_) => fs::copy(&config.file, &config.backup).map(|_| ()),
+            Err(_) => Ok(()),
+        })
+        .and_then(|_| fs::rename(&tmp, &config.file));
+    if let Err(e) = res {
+        eprintln!("Failed to write {}: {e}", config.file);
+    }
+}
+
+#[hyperfold_engine::global]
+struct SaveState {
+    pub config: SaveConfig,
+}
+
+impl SaveState {
+    pub fn new() -> Self {
+        Self {
+            config: SaveConfig::new(),
+        }
+    }
+}
+
+// Writes the game state to disk
+#[hyperfold_engine::event]
+struct SaveGame;
+
+#[hyperfold_engine::event]
+struct LoadSave(pub SaveData);
+
+#[hyperfold_engine::component(Singleton)]
+struct Autosave;
+
+#[hyperfold_engine::system(Init)]
+fn init_save(entities: &mut dyn Components, events: &mut dyn Events, state: &SaveState) {
+    if let Some(data) = load(&state.config) {
+        events.new_event(LoadSave(data));
+    }
+
+    let e = Entity::new();
+    add_components!(entities, e, Autosave, Timer::new(state.config.autosave_ms));
+}
+
+#[hyperfold_engine::system]
+fn load_save(
+    LoadSave(data): &LoadSave,
+    events: &mut dyn Events,
+    dag: &mut NumDag,
+    saved_roots: &SavedRoots,
+    unlocks: &mut Unlocks,
+    autobuyers: &mut Autobuyers,
+    research: &mut ResearchQueue,
+    WizardPosMut { pos, .. }: WizardPosMut,
+    UpgradeBoxData { carousel, .. }: UpgradeBoxData,
+    RespecButtonData { button, .. }: RespecButtonData,
+    spellbook: &mut Spellbook,
+) {
+    for (key, value) in &data.roots {
+        match saved_roots.0.iter().find(|(k, ..)| k == key) {
+            Some((_, id, _)) => dag.0.set_root_by_id(*id, *value),
+            None => eprintln!("Unknown saved root: {key}"),
+        }
+    }
+    pos.0.set_pos(
+        data.wizard_pos.x,
+        data.wizard_pos.y,
+        Align::Center,
+        Align::Center,
+    );
+    carousel.set_scroll(data.upgrade_scroll);
+    unlocks.0.extend(data.unlocked.iter().cloned());
+    for (group, settings) in &data.autobuyers {
+        autobuyers.set(group, settings.clone());
+    }
+    research.set_jobs(data.research.clone());
+    button.ready_at = data.respec_ready;
+    let now = now_ms();
+    for (cd, ready) in spellbook.cooldowns.iter_mut().zip(&data.spell_ready) {
+        *cd = ready.saturating_sub(now) as u32;
+    }
+    spellbook.surge_ms = data.surge_until.saturating_sub(now) as u32;
+    for (ty, pos) in &data.wizards {
+        dag.0.update(WizardCount(*ty), |c| *c + 1.into());
+        events.new_event(SpawnWizard { ty: *ty, pos: *pos });
+    }
+
+    events.new_event(OfflineProgress {
+        elapsed_ms: now.saturating_sub(data.time),
+    });
+}
+
+components!(labels(Autosave), AutosaveTimer, timer: &'a mut Timer);
+
+#[hyperfold_engine::system]
+fn autosave(dt: &Update, AutosaveTimer { timer, .. }: AutosaveTimer, events: &mut dyn Events) {
+    if timer.add_time(dt.0) > 0 {
+        events.new_event(SaveGame);
+    }
+}
+
+// Everything that gets saved, gathered from the systems below
+fn snapshot(
+    dag: &mut NumDag,
+    saved_roots: &SavedRoots,
+    unlocks: &Unlocks,
+    autobuyers: &Autobuyers,
+    research: &ResearchQueue,
+    wizards: Vec<HiredWizards>,
+    pos: &Position,
+    carousel: &Carousel,
+    button: &RespecButton,
+    spellbook: &Spellbook,
+) -> SaveData {
+    let now = now_ms();
+    SaveData {
+        time: now,
+        roots: saved_roots
+            .0
+            .iter()
+            .map(|(key, id, default)| (key.clone(), *dag.0.get_root_by_id(*id, *default)))
+            .collect(),
+        wizard_pos: pos.0.center(),
+        upgrade_scroll: carousel.scroll(),
+        unlocked: unlocks.0.iter().cloned().collect(),
+        autobuyers: autobuyers.settings(),
+        research: research.jobs().clone(),
+        wizards: (wizards.iter())
+            .map(|w| (w.kind.0, w.pos.0.center()))
+            .collect(),
+        respec_ready: button.ready_at,
+        spell_ready: (spellbook.cooldowns.iter())
+            .map(|ms| now + *ms as u64)
+            .collect(),
+        surge_until: now + spellbook.surge_ms as u64,
+    }
+}
+
+#[hyperfold_engine::system]
+fn save_game(
+    _: &SaveGame,
+    state: &SaveState,
+    dag: &mut NumDag,
+    saved_roots: &SavedRoots,
+    unlocks: &Unlocks,
+    autobuyers: &Autobuyers,
+    research: &ResearchQueue,
+    wizards: Vec<HiredWizards>,
+    WizardPos { pos, .. }: WizardPos,
+    UpgradeBoxData { carousel, .. }: UpgradeBoxData,
+    RespecButtonData { button, .. }: RespecButtonData,
+    spellbook: &Spellbook,
+) {
+    let data = snapshot(
+        dag,
+        saved_roots,
+        unlocks,
+        autobuyers,
+        research,
+        wizards,
+        pos,
+        carousel,
+        button,
+        spellbook,
+    );
+    write(&state.config, &data);
+}
+
+// Closing the window writes directly, the game may be gone before a SaveGame event runs
+#[hyperfold_engine::system]
+fn save_on_quit(
+    _: &Quit,
+    state: &SaveState,
+    dag: &mut NumDag,
+    saved_roots: &SavedRoots,
+    unlocks: &Unlocks,
+    autobuyers: &Autobuyers,
+    research: &ResearchQueue,
+    wizards: Vec<HiredWizards>,
+    WizardPos { pos, .. }: WizardPos,
+    UpgradeBoxData { carousel, .. }: UpgradeBoxData,
+    RespecButtonData { button, .. }: RespecButtonData,
+    spellbook: &Spellbook,
+) {
+    let data = snapshot(
+        dag,
+        saved_roots,
+        unlocks,
+        autobuyers,
+        research,
+        wizards,
+        pos,
+        carousel,
+        button,
+        spellbook,
+    );
+    write(&state.config, &data);
+}
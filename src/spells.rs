@@ -0,0 +1,400 @@
+use std::f32::consts::TAU;
+
+use hyperfold_engine::{
+    add_components, components,
+    ecs::{
+        entities::{Entity, NewEntity},
+        events::core::Update,
+    },
+    framework::{
+        event_system::events::Click,
+        physics::Position,
+        render_system::{
+            render_data::{RenderAsset, RenderTexture},
+            AssetManager, RenderComponent, RenderOpts, Renderer, Texture,
+        },
+    },
+    sdl2::SDL_Color,
+    utils::{
+        colors::{GRAY, WHITE},
+        number::Number,
+        rect::{Align, PointF, Rect},
+    },
+};
+
+use crate::{
+    _engine::{Components, Events},
+    archetypes::{WizardPower, WizardType},
+    crystal::CrystalPos,
+    effects::spawn_floating_text,
+    enemies::enemy::Enemies,
+    equation,
+    fireball::CreateFireball,
+    health::{HealthNodes, WizardHealth},
+    input::{Action, ActionPressed, Paused},
+    layout::LayoutSize,
+    param_dag::{Node, NodeDefault, NodeTrait, NumDag, Root},
+    parameters,
+    save::SavedRoots,
+    stats::{Stat, StatAdd, StatMult},
+    targeting::Targeting,
+    utils::elevations::Elevations,
+    wizard::WizardPosMut,
+};
+
+// Mana: spent on spells, regenerates over time
+// SurgeMult: multiplier on wizard power while Magic Surge is active
+parameters!(SpellNumbers(Mana = 100, SurgeMult = 1));
+// SpellPower: scales the magnitude of every spell
+parameters!(SpellNodes(SpellPower));
+
+const MAX_MANA: f64 = 100.0;
+// Mana per second
+const MANA_REGEN: f64 = 5.0;
+
+const SWEEP_COLOR: SDL_Color = SDL_Color {
+    r: 0,
+    g: 0,
+    b: 0,
+    a: 160,
+};
+const MANA_COLOR: SDL_Color = SDL_Color {
+    r: 64,
+    g: 96,
+    b: 255,
+    a: 255,
+};
+
+// Active abilities of the player's wizard
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Spell {
+    // Ring of homing fireballs around the wizard
+    Barrage,
+    // Multiplies wizard power for a while
+    Surge,
+    // Teleports the wizard to the crystal and heals it
+    Blink,
+}
+
+pub struct SpellDef {
+    pub name: &'static str,
+    pub icon: &'static str,
+    pub action: Action,
+    pub mana: f64,
+    pub cooldown_ms: u32,
+}
+
+static SPELLS: [SpellDef; 3] = [
+    SpellDef {
+        name: "Fireball Barrage",
+        icon: "res/projectiles/fireball2.png",
+        action: Action::Ability1,
+        mana: 30.0,
+        cooldown_ms: 20000,
+    },
+    SpellDef {
+        name: "Magic Surge",
+        icon: "res/wizards/catalyst.png",
+        action: Action::Ability2,
+        mana: 50.0,
+        cooldown_ms: 60000,
+    },
+    SpellDef {
+        name: "Blink",
+        icon: "res/wizards/crystal.png",
+        action: Action::Ability3,
+        mana: 20.0,
+        cooldown_ms: 15000,
+    },
+];
+
+// Magnitudes at spell power 1
+const BARRAGE_FIREBALLS: f64 = 8.0;
+const SURGE_MS: u32 = 10000;
+// Fraction of max HP healed by Blink
+const BLINK_HEAL: f64 = 0.25;
+
+impl Spell {
+    pub const ALL: [Spell; 3] = [Spell::Barrage, Spell::Surge, Spell::Blink];
+
+    pub fn def(&self) -> &'static SpellDef {
+        &SPELLS[*self as usize]
+    }
+}
+
+#[hyperfold_engine::system(Init)]
+fn init_spell_numbers(dag: &mut NumDag, saved_roots: &mut SavedRoots) {
+    dag.0.add_node(
+        SpellNodes::SpellPower,
+        equation!(
+            |(a: StatAdd(Stat::SpellPower), m: StatMult(Stat::SpellPower),)| (*a + 1.into()) * *m
+        ),
+    );
+
+    saved_roots.add("mana", SpellNumbers::Mana);
+}
+
+#[hyperfold_engine::global]
+struct Spellbook {
+    // Time until each spell can be cast again, indexed by Spell
+    pub cooldowns: [u32; 3],
+    // Time left on Magic Surge, SurgeMult follows it in update_spells
+    pub surge_ms: u32,
+}
+
+impl Spellbook {
+    pub fn new() -> Self {
+        Self {
+            cooldowns: [0; 3],
+            surge_ms: 0,
+        }
+    }
+
+    pub fn cooldown(&self, spell: Spell) -> u32 {
+        self.cooldowns[spell as usize]
+    }
+}
+
+#[hyperfold_engine::event]
+struct CastSpell(pub Spell);
+
+#[hyperfold_engine::system]
+fn spell_keys(ActionPressed(action): &ActionPressed, events: &mut dyn Events) {
+    if let Some(spell) = Spell::ALL.into_iter().find(|s| s.def().action == *action) {
+        events.new_event(CastSpell(spell));
+    }
+}
+
+#[hyperfold_engine::system]
+fn cast_spell(
+    CastSpell(spell): &CastSpell,
+    WizardPosMut { pos, .. }: WizardPosMut,
+    CrystalPos { pos: crys_pos, .. }: CrystalPos,
+    enemies: Vec<Enemies>,
+    spellbook: &mut Spellbook,
+    targeting: &Targeting,
+    paused: &Paused,
+    entities: &mut dyn Components,
+    events: &mut dyn Events,
+    dag: &mut NumDag,
+) {
+    let def = spell.def();
+    if paused.0 || spellbook.cooldown(*spell) > 0 {
+        return;
+    }
+    let c = pos.0.center();
+    let text_pos = PointF {
+        x: c.x,
+        y: c.y - pos.0.h,
+    };
+    if *dag.0.get(SpellNumbers::Mana) < def.mana.into() {
+        spawn_floating_text(entities, "Not enough mana", text_pos, GRAY, 1000);
+        return;
+    }
+    dag.0
+        .update(SpellNumbers::Mana, |m| *m - Number::from(def.mana));
+    spellbook.cooldowns[*spell as usize] = def.cooldown_ms;
+
+    let power = f64::from(*dag.0.get(SpellNodes::SpellPower));
+    match spell {
+        Spell::Barrage => {
+            let value = *dag.0.get(WizardPower(WizardType::Fire));
+            let n = (BARRAGE_FIREBALLS * power).round().max(1.0) as u32;
+            let r = pos.0.w / 2.0;
+            for i in 0..n {
+                let a = TAU * i as f32 / n as f32;
+                let p = PointF {
+                    x: c.x + r * a.cos(),
+                    y: c.y + r * a.sin(),
+                };
+                // With nothing in range the barrage flies to the crystal instead
+                events.new_event(CreateFireball {
+                    pos: p,
+                    value,
+                    target: targeting.pick(p, &enemies),
+                });
+            }
+        }
+        Spell::Surge => spellbook.surge_ms = SURGE_MS,
+        Spell::Blink => {
+            let cc = crys_pos.0.center();
+            pos.0.set_pos(cc.x, cc.y, Align::Center, Align::Center);
+            let max_hp = *dag.0.get(HealthNodes::MaxHp);
+            let heal = max_hp * Number::from(BLINK_HEAL * power);
+            dag.0
+                .update(WizardHealth::Hp, |hp| match *hp + heal < max_hp {
+                    true => *hp + heal,
+                    false => max_hp,
+                });
+        }
+    }
+
+    spawn_floating_text(entities, def.name, text_pos, WHITE, 1000);
+}
+
+#[hyperfold_engine::system]
+fn update_spells(
+    Update(dt): &Update,
+    spellbook: &mut Spellbook,
+    paused: &Paused,
+    dag: &mut NumDag,
+) {
+    if paused.0 {
+        return;
+    }
+    for cd in spellbook.cooldowns.iter_mut() {
+        *cd = cd.saturating_sub(*dt);
+    }
+
+    // SurgeMult isn't saved, so a loaded surge only applies once offline progress is done
+    spellbook.surge_ms = spellbook.surge_ms.saturating_sub(*dt);
+    let surge = match spellbook.surge_ms > 0 {
+        true => Number::from(1.0 + f64::from(*dag.0.get(SpellNodes::SpellPower))),
+        false => 1.into(),
+    };
+    if *dag.0.get(SpellNumbers::SurgeMult) != surge {
+        dag.0.set(SpellNumbers::SurgeMult, surge);
+    }
+
+    let mana = f64::from(*dag.0.get(SpellNumbers::Mana));
+    if mana < MAX_MANA {
+        let mana = (mana + MANA_REGEN * *dt as f64 / 1000.0).min(MAX_MANA);
+        dag.0.set(SpellNumbers::Mana, mana.into());
+    }
+}
+
+// Spell bar at the bottom center, one icon per spell with a cooldown sweep over it
+// and the mana bar above
+#[hyperfold_engine::component]
+struct SpellSlot(Spell);
+
+#[hyperfold_engine::component]
+struct SpellSweep(Spell);
+
+#[hyperfold_engine::component]
+struct ManaBar {
+    fill: bool,
+}
+
+#[hyperfold_engine::system(Init)]
+fn init_spell_bar(entities: &mut dyn Components, r: &Renderer, am: &mut AssetManager) {
+    for spell in Spell::ALL {
+        let e = Entity::new();
+        add_components!(
+            entities,
+            e,
+            SpellSlot(spell),
+            Position(Rect::new()),
+            RenderOpts::new(Elevations::SpellBar as u8).absolute(),
+            RenderComponent::new(RenderAsset::from_file(spell.def().icon, r, am)),
+        );
+
+        let e = Entity::new();
+        add_components!(
+            entities,
+            e,
+            SpellSweep(spell),
+            Position(Rect::new()),
+            RenderOpts::new(Elevations::SpellBarFill as u8).absolute(),
+            RenderComponent::new(RenderTexture::new(Some(Texture::new(r, 1, 1, SWEEP_COLOR)))),
+        );
+    }
+
+    for (fill, color, elevation) in [
+        (false, GRAY, Elevations::SpellBar),
+        (true, MANA_COLOR, Elevations::SpellBarFill),
+    ] {
+        let e = Entity::new();
+        add_components!(
+            entities,
+            e,
+            ManaBar { fill },
+            Position(Rect::new()),
+            RenderOpts::new(elevation as u8).absolute(),
+            RenderComponent::new(RenderTexture::new(Some(Texture::new(r, 1, 1, color)))),
+        );
+    }
+}
+
+components!(SpellSlots, slot: &'a SpellSlot, pos: &'a mut Position);
+components!(SpellSweeps, sweep: &'a SpellSweep, pos: &'a mut Position);
+components!(ManaBars, bar: &'a ManaBar, pos: &'a mut Position);
+
+const SLOT: f32 = 60.0;
+const GAP: f32 = 10.0;
+
+// Rect of the spell's icon
+fn slot_rect(spell: Spell, size: &LayoutSize) -> Rect {
+    let n = Spell::ALL.len() as f32;
+    let x = (size.w as f32 - n * SLOT - (n - 1.0) * GAP) / 2.0;
+    Rect::from(
+        x + spell as u8 as f32 * (SLOT + GAP),
+        size.h as f32 - GAP,
+        SLOT,
+        SLOT,
+        Align::TopLeft,
+        Align::BotRight,
+    )
+}
+
+#[hyperfold_engine::system]
+fn update_spell_bar(
+    _: &Update,
+    slots: Vec<SpellSlots>,
+    sweeps: Vec<SpellSweeps>,
+    bars: Vec<ManaBars>,
+    spellbook: &Spellbook,
+    size: &LayoutSize,
+    dag: &mut NumDag,
+) {
+    const H: f32 = 8.0;
+
+    for SpellSlots { slot, pos, .. } in slots {
+        pos.0 = slot_rect(slot.0, size);
+    }
+
+    let mana = f64::from(*dag.0.get(SpellNumbers::Mana));
+    for SpellSweeps { sweep, pos, .. } in sweeps {
+        let spell = sweep.0;
+        // Shrinks from the top as the cooldown runs out, covers spells that can't be paid for
+        let frac = match mana < spell.def().mana {
+            true => 1.0,
+            false => spellbook.cooldown(spell) as f32 / spell.def().cooldown_ms as f32,
+        };
+        let rect = slot_rect(spell, size);
+        let h = rect.h * frac;
+        pos.0 = Rect::from(
+            rect.x,
+            rect.y + rect.h - h,
+            rect.w,
+            h,
+            Align::TopLeft,
+            Align::TopLeft,
+        );
+    }
+
+    let first = slot_rect(Spell::Barrage, size);
+    let last = slot_rect(Spell::Blink, size);
+    let w = last.x + last.w - first.x;
+    let frac = (mana / MAX_MANA).clamp(0.0, 1.0) as f32;
+    for ManaBars { bar, pos, .. } in bars {
+        pos.0 = Rect::from(
+            first.x,
+            first.y - GAP,
+            match bar.fill {
+                true => w * frac,
+                false => w,
+            },
+            H,
+            Align::TopLeft,
+            Align::BotRight,
+        );
+    }
+}
+
+#[hyperfold_engine::system]
+fn click_spell(m: &Click, slots: Vec<SpellSlots>, events: &mut dyn Events) {
+    if let Some(s) = slots.into_iter().find(|s| m.is_me(s.eid)) {
+        events.new_event(CastSpell(s.slot.0));
+    }
+}
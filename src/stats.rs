@@ -0,0 +1,113 @@
+use hyperfold_engine::utils::number::Number;
+
+use crate::{
+    archetypes::{WizardPower, WizardType},
+    health::HealthNodes,
+    param_dag::{Node, NodeId, NodeTrait},
+    spells::SpellNodes,
+    wizard::WizardNumbers,
+};
+
+// Stats that upgrades can modify
+#[derive(Copy, Clone, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Stat {
+    // Every wizard
+    Power,
+    // A single wizard type
+    FireWizard,
+    PowerWizard,
+    CatalystWizard,
+    FireRate,
+    FireballSpeed,
+    MoveSpeed,
+    MaxHp,
+    // Magnitude of every spell
+    SpellPower,
+}
+
+impl Stat {
+    pub const ALL: [Stat; 9] = [
+        Stat::Power,
+        Stat::FireWizard,
+        Stat::PowerWizard,
+        Stat::CatalystWizard,
+        Stat::FireRate,
+        Stat::FireballSpeed,
+        Stat::MoveSpeed,
+        Stat::MaxHp,
+        Stat::SpellPower,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Stat::Power => "power",
+            Stat::FireWizard => "fire_wizard",
+            Stat::PowerWizard => "power_wizard",
+            Stat::CatalystWizard => "catalyst_wizard",
+            Stat::FireRate => "fire_rate",
+            Stat::FireballSpeed => "fireball_speed",
+            Stat::MoveSpeed => "move_speed",
+            Stat::MaxHp => "max_hp",
+            Stat::SpellPower => "spell_power",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|s| s.name() == name)
+    }
+
+    // Display name for the UI
+    pub fn label(&self) -> &'static str {
+        match self {
+            Stat::Power => "Power",
+            Stat::FireWizard => "Fire Wizard Power",
+            Stat::PowerWizard => "Power Wizard Power",
+            Stat::CatalystWizard => "Catalyst Wizard Power",
+            Stat::FireRate => "Fire Rate",
+            Stat::FireballSpeed => "Fireball Speed",
+            Stat::MoveSpeed => "Move Speed",
+            Stat::MaxHp => "Max HP",
+            Stat::SpellPower => "Spell Power",
+        }
+    }
+
+    // Final value of the stat, after modifiers
+    pub fn node_id(&self) -> NodeId {
+        match self {
+            Stat::Power => WizardNumbers::Power.id(),
+            Stat::FireWizard => WizardPower(WizardType::Fire).id(),
+            Stat::PowerWizard => WizardPower(WizardType::Power).id(),
+            Stat::CatalystWizard => WizardPower(WizardType::Catalyst).id(),
+            Stat::FireRate => WizardNumbers::FireRate.id(),
+            Stat::FireballSpeed => WizardNumbers::FireballSpeed.id(),
+            Stat::MoveSpeed => WizardNumbers::MoveSpeed.id(),
+            Stat::MaxHp => HealthNodes::MaxHp.id(),
+            Stat::SpellPower => SpellNodes::SpellPower.id(),
+        }
+    }
+}
+
+// Sum of additive upgrade effects on a stat
+#[derive(Copy, Clone)]
+pub struct StatAdd(pub Stat);
+
+impl NodeTrait for StatAdd {
+    fn idx(&self) -> u8 {
+        self.0 as u8
+    }
+}
+
+impl Node<Number> for StatAdd {}
+
+// Product of multiplicative upgrade effects on a stat
+#[derive(Copy, Clone)]
+pub struct StatMult(pub Stat);
+
+impl NodeTrait for StatMult {
+    fn idx(&self) -> u8 {
+        self.0 as u8
+    }
+}
+
+impl Node<Number> for StatMult {}
@@ -0,0 +1,96 @@
+use hyperfold_engine::{
+    _engine::Entity,
+    utils::{colors::WHITE, rect::PointF},
+};
+
+use crate::{
+    _engine::Components,
+    effects::spawn_floating_text,
+    enemies::enemy::Enemies,
+    input::{Action, ActionPressed},
+    wizard::WizardPos,
+};
+
+// Which enemy wizards outside the crystal fire at
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum TargetPolicy {
+    Nearest,
+    LowestHp,
+    // Most contact damage
+    HighestThreat,
+}
+
+impl TargetPolicy {
+    pub fn next(self) -> Self {
+        match self {
+            TargetPolicy::Nearest => TargetPolicy::LowestHp,
+            TargetPolicy::LowestHp => TargetPolicy::HighestThreat,
+            TargetPolicy::HighestThreat => TargetPolicy::Nearest,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            TargetPolicy::Nearest => "Nearest",
+            TargetPolicy::LowestHp => "Lowest HP",
+            TargetPolicy::HighestThreat => "Highest threat",
+        }
+    }
+}
+
+#[hyperfold_engine::global]
+struct Targeting {
+    pub policy: TargetPolicy,
+    // Enemies further than this from the wizard are ignored
+    pub range: f32,
+}
+
+impl Targeting {
+    pub fn new() -> Self {
+        Self {
+            policy: TargetPolicy::Nearest,
+            range: 400.0,
+        }
+    }
+
+    // Picks an enemy in range of the point, ties go to the nearest
+    pub fn pick(&self, from: PointF, enemies: &[Enemies]) -> Option<Entity> {
+        let in_range = (enemies.iter())
+            .map(|e| (e, e.pos.0.center().dist(from)))
+            .filter(|(_, d)| *d <= self.range);
+        match self.policy {
+            TargetPolicy::Nearest => in_range.min_by(|(_, d1), (_, d2)| d1.total_cmp(d2)),
+            TargetPolicy::LowestHp => in_range
+                .min_by(|(e1, d1), (e2, d2)| e1.enemy.hp.cmp(&e2.enemy.hp).then(d1.total_cmp(d2))),
+            TargetPolicy::HighestThreat => in_range.min_by(|(e1, d1), (e2, d2)| {
+                (e2.enemy.damage.total_cmp(&e1.enemy.damage)).then(d1.total_cmp(d2))
+            }),
+        }
+        .map(|(e, _)| *e.eid)
+    }
+}
+
+#[hyperfold_engine::system]
+fn cycle_target_policy(
+    ActionPressed(action): &ActionPressed,
+    targeting: &mut Targeting,
+    WizardPos { pos, .. }: WizardPos,
+    entities: &mut dyn Components,
+) {
+    if *action != Action::CycleTarget {
+        return;
+    }
+
+    targeting.policy = targeting.policy.next();
+    let p = pos.0.center();
+    spawn_floating_text(
+        entities,
+        &format!("Target: {}", targeting.policy.label()),
+        PointF {
+            x: p.x,
+            y: p.y - pos.0.h,
+        },
+        WHITE,
+        1000,
+    );
+}
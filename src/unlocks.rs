@@ -0,0 +1,20 @@
+use std::collections::HashSet;
+
+// Names of content that has been unlocked, persisted with the save
+#[hyperfold_engine::global]
+struct Unlocks(pub HashSet<String>);
+
+impl Unlocks {
+    pub fn new() -> Self {
+        Self(HashSet::new())
+    }
+
+    pub fn is_unlocked(&self, name: &str) -> bool {
+        self.0.contains(name)
+    }
+
+    // Returns true if the content wasn't already unlocked
+    pub fn unlock(&mut self, name: &str) -> bool {
+        self.0.insert(name.to_string())
+    }
+}
@@ -0,0 +1,444 @@
+use std::{any::TypeId, collections::HashMap, fs, path::Path};
+
+use hyperfold_engine::{
+    framework::render_system::{AssetManager, Renderer},
+    utils::{number::Number, traits::Id},
+};
+
+use crate::{
+    _engine::{Components, Events},
+    catalyst::CatalystNumbers,
+    crystal::CrystalNumbers,
+    param_dag::{Dag, NodeDefault, NodeId, NodeTrait, NumDag},
+    save::SavedRoots,
+    stats::{Stat, StatAdd, StatMult},
+    upgrades::{spawn_upgrade, Cost, Upgrade, UpgradeLevel, UpgradeSpent, DEFAULT_ICON},
+};
+
+const UPGRADES_FILE: &str = "res/upgrades.txt";
+
+// How an effect changes its stat at a given level
+#[derive(Copy, Clone, PartialEq)]
+pub enum EffectKind {
+    // stat + amount * level
+    Add,
+    // stat * (1 + amount * level)
+    Mult,
+    // stat * amount^level
+    Pow,
+}
+
+#[derive(Copy, Clone)]
+pub struct Effect {
+    pub stat: Stat,
+    pub kind: EffectKind,
+    pub amount: f64,
+}
+
+impl Effect {
+    // Contribution to StatAdd or StatMult at the given level
+    pub fn value(&self, level: &Number) -> Number {
+        match self.kind {
+            EffectKind::Add => Number::from(self.amount) * *level,
+            EffectKind::Mult => Number::from(self.amount) * *level + 1.into(),
+            EffectKind::Pow => Number::from(self.amount).powf(f64::from(*level)),
+        }
+    }
+}
+
+// Condition for revealing or unlocking an upgrade
+#[derive(Clone)]
+pub enum Prereq {
+    // Another upgrade is at least this level
+    Upgrade {
+        id: String,
+        // Filled in once all upgrades are known
+        root: UpgradeLevel,
+        level: u32,
+    },
+    // Lifetime magic is at least this much
+    Magic(f64),
+    Prestiges(u32),
+}
+
+impl Prereq {
+    pub fn met(&self, dag: &mut Dag<Number>) -> bool {
+        match self {
+            Prereq::Upgrade { root, level, .. } => f64::from(*dag.get(*root)) >= *level as f64,
+            Prereq::Magic(m) => *dag.get(CrystalNumbers::LifetimeMagic) >= Number::from(*m),
+            Prereq::Prestiges(n) => f64::from(*dag.get(CatalystNumbers::Prestiges)) >= *n as f64,
+        }
+    }
+
+    pub fn describe(&self, names: impl Fn(&str) -> String) -> String {
+        match self {
+            Prereq::Upgrade { id, level, .. } => format!("{} Lv {level}", names(id)),
+            Prereq::Magic(m) => format!("{} lifetime magic", Number::from(*m)),
+            Prereq::Prestiges(1) => "1 catalyze".to_string(),
+            Prereq::Prestiges(n) => format!("{n} catalyzes"),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct UpgradeDef {
+    pub id: String,
+    pub name: String,
+    // Name of the upgrade box the upgrade is shown in, see UpgradeGroups
+    pub group: String,
+    pub icon: String,
+    pub description: String,
+    pub cost: Cost,
+    pub effects: Vec<Effect>,
+    // Shown greyed out until met
+    pub requires: Vec<Prereq>,
+    // Hidden until met
+    pub reveal: Vec<Prereq>,
+    pub max_level: u32,
+    // Levels take this long to complete after being bought, see ResearchQueue
+    pub research_ms: Option<u64>,
+}
+
+// Maps group names used in the definitions file to upgrade box ids
+#[hyperfold_engine::global]
+struct UpgradeGroups(HashMap<String, TypeId>);
+
+impl UpgradeGroups {
+    pub fn new() -> Self {
+        Self(HashMap::new())
+    }
+
+    pub fn add(&mut self, name: &str, t: impl Id) {
+        self.0.insert(name.to_string(), t.type_id());
+    }
+
+    pub fn name(&self, id: TypeId) -> Option<&str> {
+        (self.0.iter())
+            .find(|(_, t)| **t == id)
+            .map(|(name, _)| name.as_str())
+    }
+}
+
+// Parsing
+// [id] starts a new upgrade, followed by `key = value` lines
+struct Section {
+    line: usize,
+    id: String,
+    fields: Vec<(usize, String, String)>,
+}
+
+fn parse_num<T: std::str::FromStr>(s: &str, what: &str) -> Result<T, String> {
+    s.parse().map_err(|_| format!("Invalid {what} '{s}'"))
+}
+
+fn parse_base(s: &str) -> Result<f64, String> {
+    match parse_num(s, "base")? {
+        b if b <= 0.0 => Err(format!("Base must be positive, got {b}")),
+        b => Ok(b),
+    }
+}
+
+fn parse_cost(s: &str) -> Result<Cost, String> {
+    match s.split_whitespace().collect::<Vec<_>>()[..] {
+        ["geometric", base, ratio] => Ok(Cost::Geometric {
+            base: parse_base(base)?,
+            ratio: match parse_num(ratio, "ratio")? {
+                r if r <= 0.0 => return Err(format!("Ratio must be positive, got {r}")),
+                r => r,
+            },
+        }),
+        ["polynomial", base, power] => Ok(Cost::Polynomial {
+            base: parse_base(base)?,
+            power: match parse_num(power, "power")? {
+                p if p > 3 => return Err(format!("Power must be 0, 1, 2 or 3, got {p}")),
+                p => p,
+            },
+        }),
+        _ => Err(format!(
+            "Expected 'geometric <base> <ratio>' or 'polynomial <base> <power>', got '{s}'"
+        )),
+    }
+}
+
+fn parse_effect(s: &str) -> Result<Effect, String> {
+    match s.split_whitespace().collect::<Vec<_>>()[..] {
+        [stat, kind, amount] => Ok(Effect {
+            stat: Stat::from_name(stat).ok_or_else(|| {
+                let stats = Stat::ALL.map(|s| s.name()).join(", ");
+                format!("Unknown stat '{stat}', expected one of: {stats}")
+            })?,
+            kind: match kind {
+                "add" => EffectKind::Add,
+                "mult" => EffectKind::Mult,
+                "pow" => EffectKind::Pow,
+                _ => return Err(format!("Unknown effect '{kind}', expected add, mult or pow")),
+            },
+            amount: parse_num(amount, "amount")?,
+        }),
+        _ => Err(format!("Expected '<stat> add|mult|pow <amount>', got '{s}'")),
+    }
+}
+
+fn parse_prereq(s: &str) -> Result<Prereq, String> {
+    match s.split_whitespace().collect::<Vec<_>>()[..] {
+        ["upgrade", id, level] => Ok(Prereq::Upgrade {
+            id: id.to_string(),
+            root: UpgradeLevel(0),
+            level: parse_num(level, "level")?,
+        }),
+        ["magic", amount] => Ok(Prereq::Magic(parse_num(amount, "amount")?)),
+        ["prestiges", n] => Ok(Prereq::Prestiges(parse_num(n, "count")?)),
+        _ => Err(format!(
+            "Expected 'upgrade <id> <level>', 'magic <amount>' or 'prestiges <count>', got '{s}'"
+        )),
+    }
+}
+
+fn parse_section(sec: &Section, err: &mut impl FnMut(usize, String)) -> Option<UpgradeDef> {
+    let mut strings: HashMap<&str, Option<String>> =
+        ["name", "group", "icon", "description", "cost", "max_level", "research"]
+            .into_iter()
+            .map(|k| (k, None))
+            .collect();
+    let (mut effects, mut requires, mut reveal) = (Vec::new(), Vec::new(), Vec::new());
+    let mut cost = None;
+    let mut max_level = u32::MAX;
+    let mut research_ms = None;
+
+    for (line, key, value) in &sec.fields {
+        let res = match key.as_str() {
+            "effect" => parse_effect(value).map(|e| effects.push(e)),
+            "requires" => parse_prereq(value).map(|p| requires.push(p)),
+            "reveal" => parse_prereq(value).map(|p| reveal.push(p)),
+            k => match strings.get_mut(k) {
+                Some(Some(_)) => Err(format!("Duplicate field '{k}'")),
+                Some(s) => {
+                    *s = Some(value.clone());
+                    match k {
+                        "cost" => parse_cost(value).map(|c| cost = Some(c)),
+                        "max_level" => parse_num(value, "max_level").map(|l| max_level = l),
+                        "research" => parse_num::<f64>(value, "research time")
+                            .map(|s| research_ms = Some((s * 1000.0) as u64)),
+                        _ => Ok(()),
+                    }
+                }
+                None => Err(format!("Unknown field '{k}'")),
+            },
+        };
+        if let Err(e) = res {
+            err(*line, e);
+        }
+    }
+
+    for k in ["name", "group", "cost"] {
+        if strings[k].is_none() {
+            err(sec.line, format!("Upgrade '{}' is missing '{k}'", sec.id));
+        }
+    }
+    if effects.is_empty() {
+        err(sec.line, format!("Upgrade '{}' has no effects", sec.id));
+    }
+
+    Some(UpgradeDef {
+        id: sec.id.clone(),
+        name: strings.remove("name").flatten()?,
+        group: strings.remove("group").flatten()?,
+        icon: strings
+            .remove("icon")
+            .flatten()
+            .unwrap_or(DEFAULT_ICON.to_string()),
+        description: strings.remove("description").flatten().unwrap_or_default(),
+        cost: cost?,
+        effects,
+        requires,
+        reveal,
+        max_level,
+        research_ms,
+    })
+}
+
+// Returns the definitions or a list of readable errors
+pub fn parse_defs(text: &str, file: &str) -> Result<Vec<UpgradeDef>, Vec<String>> {
+    let mut errs = Vec::new();
+    let mut err = |line: usize, msg: String| errs.push(format!("{file}:{line}: {msg}"));
+
+    // Split into sections
+    let mut sections: Vec<Section> = Vec::new();
+    for (i, line) in text.lines().enumerate().map(|(i, l)| (i + 1, l.trim())) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(id) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            sections.push(Section {
+                line: i,
+                id: id.trim().to_string(),
+                fields: Vec::new(),
+            });
+            continue;
+        }
+        match (line.split_once('='), sections.last_mut()) {
+            (Some((k, v)), Some(sec)) => {
+                sec.fields
+                    .push((i, k.trim().to_string(), v.trim().to_string()))
+            }
+            (Some(_), None) => err(i, "Field outside of an [upgrade] section".to_string()),
+            (None, _) => err(i, format!("Expected '[id]' or 'key = value', got '{line}'")),
+        }
+    }
+
+    let mut defs: Vec<_> = sections
+        .iter()
+        .filter_map(|sec| parse_section(sec, &mut err))
+        .collect();
+
+    // Cross-upgrade validation
+    let lines: HashMap<_, _> = sections.iter().map(|s| (s.id.as_str(), s.line)).collect();
+    for (i, sec) in sections.iter().enumerate() {
+        if sections[..i].iter().any(|s| s.id == sec.id) {
+            err(sec.line, format!("Duplicate upgrade id '{}'", sec.id));
+        }
+    }
+    if sections.len() > u8::MAX as usize + 1 {
+        err(0, format!("At most {} upgrades are supported", u8::MAX as usize + 1));
+    }
+    for def in &defs {
+        let line = lines[def.id.as_str()];
+        if !Path::new(&def.icon).exists() {
+            err(line, format!("Icon '{}' does not exist", def.icon));
+        }
+        for id in upgrade_prereqs(def) {
+            if !lines.contains_key(id.as_str()) {
+                err(line, format!("Requires unknown upgrade '{id}'"));
+            }
+        }
+        if requires_cycle(&defs, &def.id, &def.id, &mut Vec::new()) {
+            err(line, format!("Upgrade '{}' requires itself", def.id));
+        }
+    }
+
+    if !errs.is_empty() {
+        return Err(errs);
+    }
+
+    // Level roots are assigned by position in the file
+    let roots: HashMap<_, _> = (defs.iter().enumerate())
+        .map(|(i, d)| (d.id.clone(), UpgradeLevel(i as u8)))
+        .collect();
+    for def in &mut defs {
+        for p in def.requires.iter_mut().chain(def.reveal.iter_mut()) {
+            if let Prereq::Upgrade { id, root, .. } = p {
+                *root = roots[id.as_str()];
+            }
+        }
+    }
+    Ok(defs)
+}
+
+// Ids of upgrades that must be leveled before this one is available
+fn upgrade_prereqs(def: &UpgradeDef) -> impl Iterator<Item = &String> {
+    (def.requires.iter().chain(&def.reveal)).filter_map(|p| match p {
+        Prereq::Upgrade { id, .. } => Some(id),
+        _ => None,
+    })
+}
+
+// Returns true if `id` (transitively) requires `target`
+fn requires_cycle<'a>(
+    defs: &'a [UpgradeDef],
+    id: &'a str,
+    target: &str,
+    seen: &mut Vec<&'a str>,
+) -> bool {
+    if seen.contains(&id) {
+        return false;
+    }
+    seen.push(id);
+    defs.iter()
+        .filter(|d| d.id == id)
+        .flat_map(upgrade_prereqs)
+        .any(|p| p == target || requires_cycle(defs, p, target, seen))
+}
+
+// Loading
+#[hyperfold_engine::event]
+struct SpawnUpgrades(pub Vec<UpgradeDef>);
+
+pub fn load_defs() -> Vec<UpgradeDef> {
+    let res = fs::read_to_string(UPGRADES_FILE)
+        .map_err(|e| vec![format!("{UPGRADES_FILE}: {e}")])
+        .and_then(|text| parse_defs(&text, UPGRADES_FILE));
+    match res {
+        Ok(defs) => defs,
+        Err(errs) => panic!("Invalid upgrade definitions:\n{}", errs.join("\n")),
+    }
+}
+
+// Splits effects into dag values and the effects applied to them
+fn effect_values(
+    effects: Vec<(UpgradeLevel, Effect)>,
+) -> (Vec<(NodeId, Option<Number>)>, Vec<Effect>) {
+    effects
+        .into_iter()
+        .map(|(l, e)| ((l.id(), l.default()), e))
+        .unzip()
+}
+
+// Registers upgrade levels and stat modifiers with the dag
+// Entities are spawned once all groups have been registered
+#[hyperfold_engine::system(Init)]
+fn init_upgrade_defs(events: &mut dyn Events, dag: &mut NumDag, saved_roots: &mut SavedRoots) {
+    let defs = load_defs();
+
+    for (i, def) in defs.iter().enumerate() {
+        saved_roots.add(&format!("upgrade.{}", def.id), UpgradeLevel(i as u8));
+        saved_roots.add(&format!("spent.{}", def.id), UpgradeSpent(i as u8));
+    }
+
+    for stat in Stat::ALL {
+        // (level, effect) for every effect on this stat
+        let (adds, mults): (Vec<_>, Vec<_>) = (defs.iter().enumerate())
+            .flat_map(|(i, d)| d.effects.iter().map(move |e| (UpgradeLevel(i as u8), *e)))
+            .filter(|(_, e)| e.stat == stat)
+            .partition(|(_, e)| e.kind == EffectKind::Add);
+
+        let (values, adds) = effect_values(adds);
+        dag.0.add_node_dyn(StatAdd(stat), values, move |levels| {
+            (levels.iter().zip(&adds)).fold(0.into(), |s, (l, e)| s + e.value(l))
+        });
+        let (values, mults) = effect_values(mults);
+        dag.0.add_node_dyn(StatMult(stat), values, move |levels| {
+            (levels.iter().zip(&mults)).fold(1.into(), |p, (l, e)| p * e.value(l))
+        });
+    }
+
+    events.new_event(SpawnUpgrades(defs));
+}
+
+#[hyperfold_engine::system]
+fn spawn_upgrades(
+    SpawnUpgrades(defs): &SpawnUpgrades,
+    entities: &mut dyn Components,
+    groups: &UpgradeGroups,
+    r: &Renderer,
+    am: &mut AssetManager,
+) {
+    // Index within each group
+    let mut idxs: HashMap<&str, usize> = HashMap::new();
+    for (i, def) in defs.iter().enumerate() {
+        let group = match groups.0.get(&def.group) {
+            Some(group) => *group,
+            None => panic!(
+                "Invalid upgrade definitions:\n{UPGRADES_FILE}: Upgrade '{}' has unknown group '{}'",
+                def.id, def.group
+            ),
+        };
+        let idx = idxs.entry(&def.group).or_insert(0);
+        spawn_upgrade(
+            entities,
+            r,
+            am,
+            Upgrade::from_def(def, group, *idx, UpgradeLevel(i as u8)),
+        );
+        *idx += 1;
+    }
+}
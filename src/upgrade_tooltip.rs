@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+
+use hyperfold_engine::{
+    add_components, components,
+    ecs::{
+        entities::{Entity, NewEntity},
+        events::core::Update,
+    },
+    framework::{
+        physics::Position,
+        render_system::{
+            font::{FontData, TIMES},
+            render_data::Fit,
+            render_text::RenderText,
+            RenderComponent, RenderOpts,
+        },
+    },
+    utils::{
+        colors::{GREEN, RED, TRANSPARENT, WHITE},
+        number::Number,
+        rect::{Align, Rect},
+        util::AsType,
+    },
+};
+
+use crate::{
+    _engine::Components,
+    crystal::CrystalNumbers,
+    param_dag::{Dag, NodeTrait, NumDag},
+    research::{ResearchQueue, ResearchStatus},
+    upgrades::{BuyMode, Upgrade, UpgradeBoxPos, UpgradeState, Upgrades},
+    utils::{elevations::Elevations, time::format_duration},
+    wizard::WizardNumbers,
+};
+
+// Info panel for the focused upgrade, shown next to the upgrade box
+#[hyperfold_engine::component(Singleton)]
+struct UpgradeTooltip {
+    text: String,
+}
+
+// Cost line, colored by affordability
+#[hyperfold_engine::component(Singleton)]
+struct UpgradeTooltipCost {
+    text: String,
+}
+
+const W: f32 = 250.0;
+const LINE_H: u32 = 20;
+
+fn tooltip_text() -> RenderText {
+    RenderText::new(FontData {
+        w: None,
+        h: Some(LINE_H),
+        sample: "Affordable in 23h 59m".to_string(),
+        file: TIMES.to_string(),
+    })
+    .with_text_align(Align::TopLeft, Align::TopLeft)
+    .with_text_color(WHITE)
+    .with_background_color(TRANSPARENT)
+    .with_dest_fit(Fit::fit_height())
+    .with_dest_align(Align::TopLeft, Align::TopLeft)
+}
+
+#[hyperfold_engine::system(Init)]
+fn init_upgrade_tooltip(entities: &mut dyn Components) {
+    let e = Entity::new();
+    add_components!(
+        entities,
+        e,
+        UpgradeTooltip {
+            text: String::new()
+        },
+        RenderOpts::new(Elevations::Upgrades as u8)
+            .absolute()
+            .is_visible(false),
+        RenderComponent::new(tooltip_text()),
+        Position(Rect::new()),
+    );
+
+    let e = Entity::new();
+    add_components!(
+        entities,
+        e,
+        UpgradeTooltipCost {
+            text: String::new()
+        },
+        RenderOpts::new(Elevations::Upgrades as u8)
+            .absolute()
+            .is_visible(false),
+        RenderComponent::new(tooltip_text()),
+        Position(Rect::new()),
+    );
+}
+
+components!(
+    UpgradeTooltipData,
+    tooltip: &'a mut UpgradeTooltip,
+    pos: &'a mut Position,
+    opts: &'a mut RenderOpts,
+    text: &'a mut RenderComponent
+);
+components!(
+    UpgradeTooltipCostData,
+    tooltip: &'a mut UpgradeTooltipCost,
+    pos: &'a mut Position,
+    opts: &'a mut RenderOpts,
+    text: &'a mut RenderComponent
+);
+
+// Name, description, level and the stat change from buying with the current mode
+// Locked upgrades show their requirements instead
+fn info_text(
+    up: &Upgrade,
+    names: &HashMap<String, String>,
+    mode: BuyMode,
+    dag: &mut Dag<Number>,
+) -> String {
+    let level = up.get_level(dag);
+    let purchase = up.purchase(dag, mode);
+    let n = purchase.map(|(n, _)| n);
+    let maxed = n.is_none();
+    let level_text = match (n, up.research_ms) {
+        (Some(n), Some(ms)) => format!(
+            "Level {level} -> {} (research {})",
+            level + n,
+            format_duration(ms)
+        ),
+        (Some(n), None) => format!("Level {level} -> {}", level + n),
+        (None, _) => format!("Level {level} (Max)"),
+    };
+    // Show the first stat the upgrade affects
+    let stat_text = match up.effects.first().map(|e| (e.stat.label(), e.stat.node_id())) {
+        _ if up.state() == UpgradeState::Locked => {
+            let name = |id: &str| names.get(id).cloned().unwrap_or(id.to_string());
+            let reqs = (up.requires.iter())
+                .filter(|p| !p.met(dag))
+                .map(|p| p.describe(name))
+                .collect::<Vec<_>>();
+            format!("Requires: {}", reqs.join(", "))
+        }
+        Some((label, node)) if !maxed => {
+            // Stats can depend on magic, so the cost is taken out too
+            let (n, cost) = purchase.unwrap_or((0, 0.into()));
+            let magic = *dag.get(CrystalNumbers::Magic) - cost;
+            let next = dag.predict_many_by_id(
+                node,
+                vec![
+                    (up.level.id(), Number::from((level + n) as f64)),
+                    (CrystalNumbers::Magic.id(), magic),
+                ],
+            );
+            format!("{label}: {} -> {next}", *dag.get_node_by_id(node))
+        }
+        Some((label, node)) => format!("{label}: {}", *dag.get_node_by_id(node)),
+        None => String::new(),
+    };
+    format!("{}\n{}\n{level_text}\n{stat_text}", up.name, up.description)
+}
+
+// Returns the cost text and whether it is affordable
+fn cost_text(
+    up: &Upgrade,
+    mode: BuyMode,
+    confirming: bool,
+    research: &ResearchQueue,
+    dag: &mut Dag<Number>,
+) -> (String, bool) {
+    match research.status(&up.key) {
+        Some(ResearchStatus::Running { left_ms }) => {
+            return (
+                format!("Researching, {} left", format_duration(left_ms)),
+                true,
+            )
+        }
+        Some(ResearchStatus::Queued(i)) => return (format!("Research queued (#{i})"), true),
+        None => (),
+    }
+
+    let (n, cost) = match up.purchase(dag, mode) {
+        Some(purchase) => purchase,
+        None => return ("Maxed".to_string(), true),
+    };
+    let magic = *dag.get(CrystalNumbers::Magic);
+    if magic >= cost {
+        let text = match confirming {
+            true => format!("Cost (x{n}): {cost}\nClick again to buy"),
+            false => format!("Cost (x{n}): {cost}"),
+        };
+        return (text, true);
+    }
+
+    let income = *dag.get(WizardNumbers::Income);
+    let wait = match income > 0.into() {
+        true => {
+            let ms = f64::from((cost - magic) / income) * 1000.0;
+            match ms.is_finite() && ms < u64::MAX as f64 {
+                true => format!("Affordable in {}", format_duration(ms.ceil() as u64)),
+                false => "Affordable never".to_string(),
+            }
+        }
+        false => "No income".to_string(),
+    };
+    (format!("Cost (x{n}): {cost}\n{wait}"), false)
+}
+
+#[hyperfold_engine::system]
+fn update_upgrade_tooltip(
+    _: &Update,
+    upgrades: Vec<Upgrades>,
+    UpgradeBoxPos {
+        up_box,
+        pos: box_pos,
+        ..
+    }: UpgradeBoxPos,
+    info: UpgradeTooltipData,
+    cost: UpgradeTooltipCostData,
+    research: &ResearchQueue,
+    dag: &mut NumDag,
+) {
+    let names: HashMap<_, _> = (upgrades.iter())
+        .map(|u| (u.up.key.clone(), u.up.name.clone()))
+        .collect();
+    let up = up_box
+        .focused()
+        .and_then(|e| upgrades.into_iter().find(|u| u.eid == &e));
+    info.opts.set_visible(up.is_some());
+    cost.opts.set_visible(up.is_some());
+    let (up, up_eid) = match up {
+        Some(Upgrades { up, eid, .. }) => (up, eid),
+        None => return,
+    };
+
+    let (x, y) = (box_pos.0.x + box_pos.0.w + 10.0, box_pos.0.y);
+    let info_h = (LINE_H * 4) as f32;
+    info.pos.0 = Rect::from(x, y, W, info_h, Align::TopLeft, Align::TopLeft);
+    cost.pos.0 = Rect::from(
+        x,
+        y + info_h,
+        W,
+        (LINE_H * 2) as f32,
+        Align::TopLeft,
+        Align::TopLeft,
+    );
+
+    let text = info_text(up, &names, up_box.buy_mode(), &mut dag.0);
+    if text != info.tooltip.text {
+        info.text
+            .try_as_mut(|t: &mut RenderText| t.set_text(&text));
+        info.tooltip.text = text;
+    }
+
+    let confirming = up_box.confirming() == Some(*up_eid);
+    let (text, affordable) = cost_text(up, up_box.buy_mode(), confirming, research, &mut dag.0);
+    if text != cost.tooltip.text {
+        cost.text.try_as_mut(|t: &mut RenderText| {
+            t.set_text(&text);
+            t.set_text_color(match affordable {
+                true => GREEN,
+                false => RED,
+            });
+        });
+        cost.tooltip.text = text;
+    }
+}
@@ -1,7 +1,4 @@
-use std::{
-    any::TypeId,
-    f32::consts::{FRAC_PI_2, PI, TAU},
-};
+use std::any::TypeId;
 
 use hyperfold_engine::{
     add_components, components,
@@ -11,71 +8,169 @@ use hyperfold_engine::{
     },
     f32,
     framework::{
-        event_system::mouse::{Drag, DragStart, DragState, DragTrigger},
+        event_system::{
+            events::{Click, MouseMove, Scroll},
+            mouse::DragTrigger,
+        },
         physics::Position,
         render_system::{
-            drawable::Canvas,
-            render_data::RenderTexture,
-            shapes::{Rectangle, ShapeTrait},
-            RenderComponent, RenderOpts, Renderer, Screen, Texture,
+            font::{FontData, TIMES},
+            render_data::{Fit, RenderAsset, RenderDataBuilderTrait, RenderTexture},
+            render_text::RenderText,
+            AssetManager, RenderComponent, RenderOpts, Renderer, Screen, Texture,
         },
     },
     utils::{
-        colors::{BLUE, GRAY, GREEN, RED},
-        math::NormalizeAngle,
-        rect::{Align, Rect},
+        colors::{BLUE, GRAY, GREEN, RED, TRANSPARENT, WHITE},
+        number::Number,
+        rect::{Align, PointF, Rect},
         traits::Id,
-        util::FloatMath,
+        util::{AsType, FloatMath},
     },
 };
 use itertools::Itertools;
 
-use crate::{_engine::Components, utils::elevations::Elevations};
+use crate::{
+    _engine::{Components, Events},
+    carousel::{Carousel, CarouselShape},
+    catalyst::Prestige,
+    crystal::{CrystalNumbers, CrystalTextPos},
+    effects::{spawn_flash, spawn_floating_text, Pulse, Shake, UiTransform},
+    input::{Action, ActionPressed},
+    layout::ScreenResized,
+    param_dag::{Dag, NodeTrait, NumDag, Root},
+    research::ResearchQueue,
+    upgrade_defs::{Effect, Prereq, UpgradeDef},
+    utils::elevations::{Elevations, UPGRADE_ICON_LAYERS},
+};
 
 // Upgrade box
+// Upgrades are laid out by the Carousel on the same entity
 #[hyperfold_engine::component(Singleton)]
 struct UpgradeBox {
-    v_scroll: f32,
-    scroll: f32,
-    min_scroll: f32,
-    max_scroll: f32,
-    upgrade_rects: Vec<(Rect, usize)>,
+    // Group being shown, kept while the box slides closed
     open_id: Option<TypeId>,
+    // Last group shown, switching groups resets the scroll
+    last_id: Option<TypeId>,
+    // Entity whose group is shown
+    owner: Option<Entity>,
+    closing: bool,
+    // Slide animation from 0 (closed) to 1 (open)
+    slide: f32,
+    // Position when fully open
+    rect: Rect,
     update: bool,
+    hovered: Option<Entity>,
+    selected: Option<Entity>,
+    // Last cursor position, hover is re-tested every frame as the carousel moves under it
+    mouse: PointF,
+    // Where the hovered/selected upgrade is previewed, in screen coordinates
+    img_rect: Rect,
+    buy_mode: BuyMode,
+    // Bulk purchases wait for a second click on the same upgrade
+    confirming: Option<Entity>,
 }
 
 impl Default for UpgradeBox {
     fn default() -> Self {
         Self {
-            v_scroll: 0.0,
-            scroll: 0.0,
-            min_scroll: 0.0,
-            max_scroll: 0.0,
-            upgrade_rects: Vec::new(),
             open_id: None,
+            last_id: None,
+            owner: None,
+            closing: false,
+            slide: 0.0,
+            rect: Rect::new(),
             update: false,
+            hovered: None,
+            selected: None,
+            mouse: PointF::new(),
+            img_rect: Rect::new(),
+            buy_mode: BuyMode::One,
+            confirming: None,
         }
     }
 }
 
-#[hyperfold_engine::system(Init)]
-fn init_upgrades(entities: &mut dyn Components, screen: &Screen, r: &Renderer) {
-    let (w, h) = (screen.0.w as f32, screen.0.h as f32);
-    let rect = Rect::from(
+impl UpgradeBox {
+    pub fn hovered(&self) -> Option<Entity> {
+        self.hovered
+    }
+
+    pub fn selected(&self) -> Option<Entity> {
+        self.selected
+    }
+
+    pub fn buy_mode(&self) -> BuyMode {
+        self.buy_mode
+    }
+
+    pub fn confirming(&self) -> Option<Entity> {
+        self.confirming
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open_id.is_some() && !self.closing
+    }
+
+    pub fn close(&mut self) {
+        self.closing = self.open_id.is_some();
+        self.confirming = None;
+    }
+
+    // Position partway through the slide animation
+    fn slide_rect(&self) -> Rect {
+        // Ease out when opening, in when closing
+        let t = 1.0 - (1.0 - self.slide).powi(2);
+        let mut rect = self.rect.clone();
+        rect.y -= (1.0 - t) * (self.rect.y + self.rect.h);
+        rect
+    }
+
+    pub fn open_id(&self) -> Option<TypeId> {
+        self.open_id
+    }
+
+    pub fn owner(&self) -> Option<Entity> {
+        self.owner
+    }
+
+    // The upgrade shown in the preview and tooltip
+    pub fn focused(&self) -> Option<Entity> {
+        self.open_id.and(self.hovered.or(self.selected))
+    }
+}
+
+// Top center, half the screen wide and a fifth tall
+fn box_rect(w: f32, h: f32) -> Rect {
+    Rect::from(
         w / 2.0,
         0.0,
         w / 2.0,
         h / 5.0,
         Align::Center,
         Align::TopLeft,
-    );
+    )
+}
+
+#[hyperfold_engine::system(Init)]
+fn init_upgrades(
+    entities: &mut dyn Components,
+    screen: &Screen,
+    r: &Renderer,
+    am: &mut AssetManager,
+) {
+    let rect = box_rect(screen.0.w as f32, screen.0.h as f32);
     let tex = Texture::new(r, rect.w_i32() as u32, rect.h_i32() as u32, GRAY);
 
     let e = Entity::new();
     add_components!(
         entities,
         e,
-        UpgradeBox::default(),
+        UpgradeBox {
+            rect: rect.clone(),
+            ..UpgradeBox::default()
+        },
+        Carousel::new(CarouselShape::default()),
         DragTrigger::OnMove,
         Position(rect),
         RenderOpts::new(Elevations::Upgrades as u8)
@@ -83,47 +178,424 @@ fn init_upgrades(entities: &mut dyn Components, screen: &Screen, r: &Renderer) {
             .is_visible(false),
         RenderComponent::new(RenderTexture::new(Some(tex)))
     );
+
+    // Preview image and level badge
+    let e = Entity::new();
+    add_components!(
+        entities,
+        e,
+        UpgradePreview { shown: None },
+        Position(Rect::new()),
+        RenderOpts::new(Elevations::UpgradePreview as u8)
+            .absolute()
+            .is_visible(false),
+        RenderComponent::new(RenderAsset::from_file(DEFAULT_ICON, r, am))
+    );
+
+    let e = Entity::new();
+    add_components!(
+        entities,
+        e,
+        UpgradeBadge,
+        Position(Rect::new()),
+        RenderOpts::new(Elevations::UpgradeBadges as u8)
+            .absolute()
+            .is_visible(false),
+        RenderComponent::new(
+            RenderText::new(FontData {
+                w: None,
+                h: Some(20),
+                sample: "Lv 999".to_string(),
+                file: TIMES.to_string()
+            })
+            .with_text_align(Align::BotRight, Align::BotRight)
+            .with_text_color(WHITE)
+            .with_background_color(TRANSPARENT)
+            .with_dest_fit(Fit::fit_height())
+            .with_dest_align(Align::BotRight, Align::BotRight)
+        )
+    );
+
+    // Buy mode toggle, below the bottom left of the box
+    let e = Entity::new();
+    add_components!(
+        entities,
+        e,
+        BuyModeButton,
+        Position(Rect::new()),
+        RenderOpts::new(Elevations::UpgradeBadges as u8)
+            .absolute()
+            .is_visible(false),
+        RenderComponent::new(
+            RenderText::new(FontData {
+                w: None,
+                h: Some(20),
+                sample: "x100".to_string(),
+                file: TIMES.to_string()
+            })
+            .with_text(BuyMode::One.label())
+            .with_text_color(WHITE)
+            .with_background_color(GRAY)
+            .with_dest_fit(Fit::fit_height())
+            .with_dest_align(Align::TopLeft, Align::TopLeft)
+        )
+    );
 }
 
 // Upgrades
+// Dag root storing an upgrade's level, must be unique across all upgrades
+#[derive(Copy, Clone)]
+pub struct UpgradeLevel(pub u8);
+
+impl NodeTrait for UpgradeLevel {
+    fn idx(&self) -> u8 {
+        self.0
+    }
+}
+
+impl Root<Number> for UpgradeLevel {
+    fn default(&self) -> Number {
+        0.into()
+    }
+}
+
+// Dag root storing the total magic spent on an upgrade, uses the same index as its level
+#[derive(Copy, Clone)]
+pub struct UpgradeSpent(pub u8);
+
+impl NodeTrait for UpgradeSpent {
+    fn idx(&self) -> u8 {
+        self.0
+    }
+}
+
+impl Root<Number> for UpgradeSpent {
+    fn default(&self) -> Number {
+        0.into()
+    }
+}
+
+// Cost in magic to buy the next level
+#[derive(Copy, Clone)]
+pub enum Cost {
+    // base * ratio^level
+    Geometric { base: f64, ratio: f64 },
+    // base * (level + 1)^power, power is at most 3 so totals have a closed form
+    Polynomial { base: f64, power: u32 },
+}
+
+impl Cost {
+    pub fn at(&self, level: u32) -> Number {
+        match *self {
+            Cost::Geometric { base, ratio } => {
+                Number::from(base) * Number::from(ratio).powf(level as f64)
+            }
+            Cost::Polynomial { base, power } => {
+                Number::from(base) * Number::from(level as f64 + 1.0).powf(power as f64)
+            }
+        }
+    }
+
+    // Total cost of buying n levels starting from level
+    pub fn total(&self, level: u32, n: u32) -> Number {
+        if n == 0 {
+            return 0.into();
+        }
+        match *self {
+            Cost::Geometric { base, .. } if self.is_flat() => Number::from(base * n as f64),
+            // Geometric series
+            Cost::Geometric { base, ratio } => {
+                let r = Number::from(ratio);
+                Number::from(base) * r.powf(level as f64) * (r.powf(n as f64) - 1.into())
+                    / Number::from(ratio - 1.0)
+            }
+            // Faulhaber's formulas for sum(x^power, x in 1..=m)
+            Cost::Polynomial { base, power } => {
+                let sum = |m: f64| match power {
+                    0 => m,
+                    1 => m * (m + 1.0) / 2.0,
+                    2 => m * (m + 1.0) * (2.0 * m + 1.0) / 6.0,
+                    _ => (m * (m + 1.0) / 2.0).powi(2),
+                };
+                let (a, b) = (level as f64, level as f64 + n as f64);
+                Number::from(base) * Number::from(sum(b) - sum(a))
+            }
+        }
+    }
+
+    // Most levels, up to max, that can be bought starting from level
+    pub fn max_affordable(&self, level: u32, magic: Number, max: u32) -> u32 {
+        let n = match *self {
+            Cost::Geometric { base, .. } if self.is_flat() => {
+                (f64::from(magic) / base).floor().min(max as f64) as u32
+            }
+            // Invert the geometric series: n = log_r(magic * (r - 1) / at(level) + 1)
+            Cost::Geometric { ratio, .. } => {
+                let x = magic * Number::from(ratio - 1.0) / self.at(level) + 1.into();
+                let n = f64::from(x.log10()) / ratio.log10();
+                match n.is_finite() && n > 0.0 {
+                    true => n.floor().min(max as f64) as u32,
+                    // Shrinking costs add up to less than magic no matter how many are bought
+                    false if ratio < 1.0 && x <= 0.into() => max,
+                    false => 0,
+                }
+            }
+            // Binary search on the closed form total
+            Cost::Polynomial { .. } => {
+                let (mut lo, mut hi) = (0, max);
+                while lo < hi {
+                    let mid = hi - (hi - lo) / 2;
+                    match self.total(level, mid) <= magic {
+                        true => lo = mid,
+                        false => hi = mid - 1,
+                    }
+                }
+                lo
+            }
+        };
+        // Correct for floating point error
+        match n > 0 && self.total(level, n) > magic {
+            true => n - 1,
+            false => n,
+        }
+    }
+
+    fn is_flat(&self) -> bool {
+        matches!(self, Cost::Geometric { ratio, .. } if *ratio == 1.0)
+    }
+}
+
+// How many levels clicking an upgrade buys
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum BuyMode {
+    One,
+    Ten,
+    Hundred,
+    // As many as can be afforded
+    Max,
+}
+
+impl BuyMode {
+    pub fn next(self) -> Self {
+        match self {
+            BuyMode::One => BuyMode::Ten,
+            BuyMode::Ten => BuyMode::Hundred,
+            BuyMode::Hundred => BuyMode::Max,
+            BuyMode::Max => BuyMode::One,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            BuyMode::One => "x1",
+            BuyMode::Ten => "x10",
+            BuyMode::Hundred => "x100",
+            BuyMode::Max => "Max",
+        }
+    }
+}
+
+pub const DEFAULT_ICON: &str = "res/default.png";
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum UpgradeState {
+    // Not shown in the upgrade box
+    Hidden,
+    // Shown but can't be bought
+    Locked,
+    Available,
+}
+
 #[hyperfold_engine::component]
 struct Upgrade {
     // Upgrades with the same ids get shown together
     id: TypeId,
     idx: usize,
+    // Id from the definitions file
+    pub key: String,
+    pub name: String,
+    pub description: String,
+    pub icon: String,
+    // Effects are Dag nodes that depend on this root
+    pub level: UpgradeLevel,
+    // Refunded when the group is respecced
+    pub spent: UpgradeSpent,
+    pub max_level: u32,
+    pub research_ms: Option<u64>,
+    pub cost: Cost,
+    pub effects: Vec<Effect>,
+    pub requires: Vec<Prereq>,
+    pub reveal: Vec<Prereq>,
+    // None until first computed
+    state: Option<UpgradeState>,
 }
 
 impl Upgrade {
-    pub fn new(t: impl Id, idx: usize) -> Self {
+    pub fn group(&self) -> TypeId {
+        self.id
+    }
+
+    pub fn idx(&self) -> usize {
+        self.idx
+    }
+
+    pub fn from_def(def: &UpgradeDef, group: TypeId, idx: usize, level: UpgradeLevel) -> Self {
         Self {
-            id: t.type_id(),
+            id: group,
             idx,
+            key: def.id.clone(),
+            name: def.name.clone(),
+            description: def.description.clone(),
+            icon: def.icon.clone(),
+            level,
+            spent: UpgradeSpent(level.0),
+            max_level: def.max_level,
+            research_ms: def.research_ms,
+            cost: def.cost,
+            effects: def.effects.clone(),
+            requires: def.requires.clone(),
+            reveal: def.reveal.clone(),
+            state: None,
+        }
+    }
+
+    pub fn state(&self) -> UpgradeState {
+        self.state.unwrap_or(UpgradeState::Hidden)
+    }
+
+    pub fn compute_state(&self, dag: &mut Dag<Number>) -> UpgradeState {
+        if !self.reveal.iter().all(|p| p.met(dag)) {
+            UpgradeState::Hidden
+        } else if !self.requires.iter().all(|p| p.met(dag)) {
+            UpgradeState::Locked
+        } else {
+            UpgradeState::Available
+        }
+    }
+
+    pub fn get_level(&self, dag: &mut Dag<Number>) -> u32 {
+        f64::from(*dag.get(self.level)) as u32
+    }
+
+    // Levels and total cost bought with the given mode, None if the upgrade is maxed
+    // Max mode previews a single level when none are affordable
+    // Research is always bought one level at a time
+    pub fn purchase(&self, dag: &mut Dag<Number>, mode: BuyMode) -> Option<(u32, Number)> {
+        let level = self.get_level(dag);
+        let left = self.max_level.saturating_sub(level);
+        let n = match mode {
+            _ if left == 0 => return None,
+            _ if self.research_ms.is_some() => 1,
+            BuyMode::One => 1,
+            BuyMode::Ten => left.min(10),
+            BuyMode::Hundred => left.min(100),
+            BuyMode::Max => {
+                let magic = *dag.get(CrystalNumbers::Magic);
+                self.cost.max_affordable(level, magic, left).max(1)
+            }
+        };
+        Some((n, self.cost.total(level, n)))
+    }
+
+    // Returns the levels bought and their cost, None if it couldn't be bought
+    // Research levels are paid for here but only applied once the research completes
+    pub fn try_buy(&self, dag: &mut Dag<Number>, mode: BuyMode) -> Option<(u32, Number)> {
+        if self.compute_state(dag) != UpgradeState::Available {
+            return None;
+        }
+        match self.purchase(dag, mode) {
+            Some((n, cost)) if *dag.get(CrystalNumbers::Magic) >= cost => {
+                dag.update(CrystalNumbers::Magic, |m| *m - cost);
+                dag.update(self.spent, |s| *s + cost);
+                if self.research_ms.is_none() {
+                    dag.update(self.level, |l| *l + Number::from(n as f64));
+                }
+                Some((n, cost))
+            }
+            _ => None,
         }
     }
 }
 
+// Spawns an upgrade with its icon
+pub fn spawn_upgrade(
+    entities: &mut dyn Components,
+    r: &Renderer,
+    am: &mut AssetManager,
+    up: Upgrade,
+) {
+    let icon = RenderComponent::new(RenderAsset::from_file(&up.icon, r, am));
+    let e = Entity::new();
+    add_components!(
+        entities,
+        e,
+        up,
+        icon,
+        Position(Rect::new()),
+        RenderOpts::new(Elevations::UpgradeIcons as u8)
+            .absolute()
+            .is_visible(false),
+        UiTransform::new(),
+        Pulse::new(1.3),
+        Shake::new(6.0),
+    );
+}
+
 components!(Upgrades, up: &'a Upgrade);
-components!(UpgradeBoxData, up_box: &'a mut UpgradeBox);
-components!(UpgradeBoxDrawArgs, up_box: &'a mut UpgradeBox, pos: &'a Position, opts: &'a mut RenderOpts, tex: &'a mut RenderComponent);
+components!(UpgradesMut, up: &'a mut Upgrade, pulse: &'a mut Pulse);
+components!(
+    UpgradeIcons,
+    up: &'a Upgrade,
+    pos: &'a mut Position,
+    opts: &'a mut RenderOpts
+);
+
+// Lets filter_upgrades() work on any query containing an Upgrade
+pub trait AsUpgrade {
+    fn upgrade(&self) -> &Upgrade;
+}
+
+impl AsUpgrade for Upgrades<'_> {
+    fn upgrade(&self) -> &Upgrade {
+        self.up
+    }
+}
+
+impl AsUpgrade for UpgradeIcons<'_> {
+    fn upgrade(&self) -> &Upgrade {
+        self.up
+    }
+}
+components!(UpgradeBoxData, up_box: &'a mut UpgradeBox, carousel: &'a mut Carousel);
+components!(UpgradeBoxPos, up_box: &'a UpgradeBox, pos: &'a Position);
+components!(UpgradeBoxMouse, up_box: &'a mut UpgradeBox, carousel: &'a mut Carousel, pos: &'a Position, opts: &'a RenderOpts);
+components!(UpgradeBoxDrawArgs, up_box: &'a mut UpgradeBox, carousel: &'a mut Carousel, pos: &'a Position, opts: &'a mut RenderOpts, tex: &'a mut RenderComponent);
 
 // Draw upgrade box
 #[hyperfold_engine::system]
 fn draw_upgrades(
     _: &PreRender,
-    upgrades: Vec<Upgrades>,
+    icons: Vec<UpgradeIcons>,
     UpgradeBoxDrawArgs {
         pos,
         up_box,
+        carousel,
         opts,
         tex,
         ..
     }: UpgradeBoxDrawArgs,
     r: &Renderer,
 ) {
-    if !up_box.update {
+    if !up_box.update && !carousel.update {
         return;
     }
 
+    // Only icons in view get shown
+    let mut icons = icons;
+    for icon in &mut icons {
+        icon.opts.set_visible(false);
+    }
+
     let id = match up_box.open_id {
         Some(id) => id,
         None => {
@@ -132,103 +604,16 @@ fn draw_upgrades(
         }
     };
 
-    let upgrades = filter_upgrades(upgrades, id);
-    let n = upgrades.len();
-
-    // Largest width of an upgrade icon
-    // Also the scroll distance between each upgrade
-    let w = pos.0.h() / 2.0;
-    let (cx, cy) = (pos.0.half_w(), pos.0.half_h() - w / 4.0);
-    // x/y radii of the upgrade ellipse
-    let (rx, ry) = ((pos.0.w() - w) / 2.0, (pos.0.h() - w) / 2.0);
-
-    let get_rect = |angle: f32| {
-        let angle_diff = ((angle + 3.0 * FRAC_PI_2) % TAU).min((5.0 * FRAC_PI_2 - angle) % TAU);
-        Rect::from(
-            cx + rx * angle.cos(),
-            cy - ry * angle.sin(),
-            w * angle_diff / PI,
-            w * angle_diff / PI,
-            Align::Center,
-            Align::Center,
-        )
-    };
-
-    // Higher means closer/more back upgrades
-    const M: f32 = 4.0;
-    // Start location of the first upgrade at 0 scroll
-    const START_ANGLE: f32 = 3.0 * FRAC_PI_2;
-
-    // Conversion from scroll distance to angle displacement
-    let scroll_to_deg = PI / pos.0.w();
-    // One width of scrolling separates each upgrade
-    let step = w * scroll_to_deg;
-    // Decays from step @ a=0 to step/M @ a = PI/2
-    let lin_da = |a: f32| step * (1.0 - a / FRAC_PI_2 * (M - 1.0) / M);
-    // x = a + da, da = lin_da(PI - x)
-    // da(a) = (step*M*pi/2 + s*(M-1)*(a-pi)) / (M*pi/2 - s*(M-1))
-    let pred_da = |a: f32| {
-        let t = step * (M - 1.0);
-        let u = M * FRAC_PI_2;
-        (step * u + t * (a - PI)) / (u - t)
-    };
-    // Solve for da when a = PI/2
-    let min_step = pred_da(FRAC_PI_2);
-    // Full function for calculating the da step CCW from the current angle
-    let da = |a: f32| {
-        match a.normalize_rad() {
-            // Linear scaling
-            a if a <= FRAC_PI_2 - min_step => lin_da(a),
-            // Constant min step to smooth accross PI/2
-            a if a <= FRAC_PI_2 => min_step,
-            // Predictive linear scaling
-            a if a <= PI - step => pred_da(a),
-            // Constant max step
-            _ => step,
-        }
-    };
-
-    // Compute angular displacement of the first upgrade
-    let mut angle = START_ANGLE - up_box.scroll * scroll_to_deg;
-    // We want to trace out the total angle from the last upgrade CW to the first upgrade
-    // However da(a) is setup to calculate angles moving CCW
-    // Instead, we flip the last upgrade accross the y-axis and trace out CCW
-    let mut max_angle = PI - START_ANGLE;
-    let mut idx = 0;
-    let mut rects = Vec::new();
-    while idx < n {
-        // Add a new rect to be rendered
-        if angle < 5.0 * FRAC_PI_2 {
-            if angle >= FRAC_PI_2 {
-                rects.push((get_rect(angle), idx, angle));
-            }
-            angle += da(angle);
-        }
-        // Compute angle cost of all upgrades
-        if idx < n - 1 {
-            max_angle += da(max_angle);
-        }
-        idx += 1;
-    }
-
-    up_box.max_scroll = (max_angle - PI + START_ANGLE) / scroll_to_deg;
-
-    up_box.upgrade_rects = rects
-        .into_iter()
-        // Sort top to bottom (render order)
-        .sorted_by(|(.., a1), (.., a2)| {
-            a1.sin()
-                .partial_cmp(&a2.sin())
-                .expect("NaN When sorting upgrade rects")
-                .reverse()
-        })
-        .map(|(r, i, _)| (r, i))
-        .collect();
+    let mut icons = filter_upgrades(icons, id);
+    let n = icons.len();
+    carousel.set_len(n);
+    carousel.layout(&pos.0);
 
     opts.set_visible(true);
 
     // Position of central image inside ellipse
-    let img_rect = Rect::from(
+    let w = pos.0.h() * carousel.shape.item_size;
+    up_box.img_rect = Rect::from(
         pos.0.cx(),
         pos.0.cy(),
         w * 2.0,
@@ -236,51 +621,241 @@ fn draw_upgrades(
         Align::Center,
         Align::Center,
     );
-    // TODO: render image
-    // mImg.setDest(imgR);
 
-    let new_tex = Texture::new(r, pos.0.w_i32() as u32, pos.0.h_i32() as u32, GRAY);
-    for (rect, idx) in &up_box.upgrade_rects {
-        new_tex.draw(
-            r,
-            &mut Rectangle::new().fill(*rect).set_color(heatmap(*idx, n)),
-        );
-    }
+    // Icons are drawn on top of the box, the background marks each slot
+    let new_tex = carousel.render(r, &pos.0, GRAY, |item, rect, _| {
+        let icon = &mut icons[item.idx];
+        // Icons are separate entities, so the draw order comes from their elevation
+        let layer = item.layer.min(UPGRADE_ICON_LAYERS as usize - 1) as u8;
+        *icon.opts = RenderOpts::new(Elevations::UpgradeIcons as u8 + layer).absolute();
+        icon.opts.set_visible(true);
+        // Locked icons are shrunk inside their slot
+        match icon.up.state() {
+            UpgradeState::Locked => {
+                icon.pos.0 =
+                    rect.with_dim(rect.w * 0.5, rect.h * 0.5, Align::Center, Align::Center);
+                LOCKED_COLOR
+            }
+            _ => {
+                icon.pos.0 = rect;
+                heatmap(item.idx, n)
+            }
+        }
+    });
     tex.set(RenderTexture::new(Some(new_tex)));
 
     up_box.update = false;
+    carousel.update = false;
+}
+
+// Preview of the hovered/selected upgrade
+#[hyperfold_engine::component(Singleton)]
+struct UpgradePreview {
+    // Upgrade and level currently displayed
+    shown: Option<(Entity, u32)>,
+}
+
+#[hyperfold_engine::component(Singleton)]
+struct UpgradeBadge;
+
+components!(
+    UpgradePreviewData,
+    preview: &'a mut UpgradePreview,
+    pos: &'a mut Position,
+    opts: &'a mut RenderOpts,
+    img: &'a mut RenderComponent
+);
+components!(
+    labels(UpgradeBadge),
+    UpgradeBadgeData,
+    pos: &'a mut Position,
+    opts: &'a mut RenderOpts,
+    text: &'a mut RenderComponent
+);
+
+#[hyperfold_engine::system]
+fn update_upgrade_preview(
+    _: &PreRender,
+    upgrades: Vec<Upgrades>,
+    UpgradeBoxData { up_box, .. }: UpgradeBoxData,
+    UpgradePreviewData {
+        preview,
+        pos,
+        opts,
+        img,
+        ..
+    }: UpgradePreviewData,
+    badge: UpgradeBadgeData,
+    dag: &mut NumDag,
+    r: &Renderer,
+    am: &mut AssetManager,
+) {
+    let up = up_box
+        .focused()
+        .and_then(|e| upgrades.into_iter().find(|u| u.eid == &e));
+    let shown = up.as_ref().map(|u| (*u.eid, u.up.get_level(&mut dag.0)));
+    // Follows the box as it slides and when the screen is resized
+    pos.0 = up_box.img_rect.clone();
+    badge.pos.0 = up_box.img_rect.clone();
+    if shown == preview.shown {
+        return;
+    }
+
+    opts.set_visible(up.is_some());
+    badge.opts.set_visible(up.is_some());
+    if let (Some(Upgrades { up, .. }), Some((eid, level))) = (up, shown) {
+        if preview.shown.map(|(e, _)| e) != Some(eid) {
+            img.set(RenderAsset::from_file(&up.icon, r, am));
+        }
+        let text = match level >= up.max_level {
+            true => "Max".to_string(),
+            false => format!("Lv {level}"),
+        };
+        badge
+            .text
+            .try_as_mut(|t: &mut RenderText| t.set_text(&text));
+    }
+    preview.shown = shown;
 }
 
 // Switch upgrade list
 #[hyperfold_engine::event]
 struct OpenUpgrades {
     id: TypeId,
+    owner: Entity,
 }
 
 impl OpenUpgrades {
+    pub fn new(t: impl Id, owner: Entity) -> Self {
+        Self {
+            id: t.type_id(),
+            owner,
+        }
+    }
+}
+
+// Clicking an entity with this opens its upgrade group
+#[hyperfold_engine::component]
+struct UpgradeOwner(TypeId);
+
+impl UpgradeOwner {
     pub fn new(t: impl Id) -> Self {
-        Self { id: t.type_id() }
+        Self(t.type_id())
     }
 }
 
-// Returns Upgrades with the given Id, sorted by index
-pub fn filter_upgrades(upgrades: Vec<Upgrades>, id: TypeId) -> Vec<Upgrades> {
+components!(UpgradeOwners, owner: &'a UpgradeOwner);
+
+// Returns visible Upgrades with the given Id, sorted by index
+pub fn filter_upgrades<U: AsUpgrade>(upgrades: Vec<U>, id: TypeId) -> Vec<U> {
     upgrades
         .into_iter()
-        .filter(|u| u.up.id == id)
-        .sorted_by_key(|u| u.up.idx)
+        .filter(|u| u.upgrade().id == id && u.upgrade().state() != UpgradeState::Hidden)
+        .sorted_by_key(|u| u.upgrade().idx)
         .collect()
 }
 
 #[hyperfold_engine::system]
 fn open_upgrades(
-    OpenUpgrades { id }: &OpenUpgrades,
-    UpgradeBoxData { up_box, .. }: UpgradeBoxData,
+    OpenUpgrades { id, owner }: &OpenUpgrades,
+    UpgradeBoxData { up_box, carousel }: UpgradeBoxData,
 ) {
+    if up_box.last_id.is_some_and(|last| last != *id) {
+        carousel.set_scroll(0.0);
+        up_box.hovered = None;
+        up_box.selected = None;
+        up_box.confirming = None;
+    }
     up_box.open_id = Some(*id);
+    up_box.last_id = Some(*id);
+    up_box.owner = Some(*owner);
+    up_box.closing = false;
+    up_box.update = true;
+}
+
+// Clicking the owner of the open group closes it
+#[hyperfold_engine::system]
+fn click_upgrade_owner(
+    m: &Click,
+    owners: Vec<UpgradeOwners>,
+    UpgradeBoxData { up_box, .. }: UpgradeBoxData,
+    events: &mut dyn Events,
+) {
+    if let Some(UpgradeOwners { eid, owner }) = owners.into_iter().find(|o| m.is_me(o.eid)) {
+        match up_box.is_open() && up_box.owner == Some(*eid) {
+            true => up_box.close(),
+            false => events.new_event(OpenUpgrades {
+                id: owner.0,
+                owner: *eid,
+            }),
+        }
+    }
+}
+
+components!(
+    UpgradeBoxSlide,
+    up_box: &'a mut UpgradeBox,
+    pos: &'a mut Position
+);
+
+// Slides the box in from above the screen, closes it when its owner is gone
+#[hyperfold_engine::system]
+fn slide_upgrade_box(
+    Update(dt): &Update,
+    owners: Vec<UpgradeOwners>,
+    UpgradeBoxSlide { up_box, pos, .. }: UpgradeBoxSlide,
+) {
+    const SLIDE_MS: f32 = 200.0;
+
+    if up_box
+        .owner
+        .is_some_and(|e| !owners.iter().any(|o| o.eid == &e))
+    {
+        up_box.owner = None;
+        up_box.close();
+    }
+
+    let target = match up_box.is_open() {
+        true => 1.0,
+        false => 0.0,
+    };
+    if up_box.slide == target {
+        return;
+    }
+
+    let ds = f32!(*dt) / SLIDE_MS;
+    up_box.slide = match up_box.slide < target {
+        true => (up_box.slide + ds).min(target),
+        false => (up_box.slide - ds).max(target),
+    };
+    if up_box.slide == 0.0 {
+        up_box.open_id = None;
+        up_box.owner = None;
+        up_box.hovered = None;
+        up_box.closing = false;
+    }
+
+    pos.0 = up_box.slide_rect();
+    up_box.update = true;
+}
+
+#[hyperfold_engine::system]
+fn layout_upgrade_box(
+    ScreenResized { w, h }: &ScreenResized,
+    UpgradeBoxSlide { up_box, pos, .. }: UpgradeBoxSlide,
+) {
+    up_box.rect = box_rect(*w, *h);
+    pos.0 = up_box.slide_rect();
     up_box.update = true;
 }
 
+const LOCKED_COLOR: hyperfold_engine::sdl2::SDL_Color = hyperfold_engine::sdl2::SDL_Color {
+    r: 64,
+    g: 64,
+    b: 64,
+    a: 255,
+};
+
 fn heatmap(i: usize, n: usize) -> hyperfold_engine::sdl2::SDL_Color {
     let colors = [RED, GREEN, BLUE];
 
@@ -301,51 +876,434 @@ fn heatmap(i: usize, n: usize) -> hyperfold_engine::sdl2::SDL_Color {
     }
 }
 
-// TODO:
-// Dragging
+// Buying, sent by clicks and autobuyers
+#[hyperfold_engine::event]
+struct BuyUpgrade {
+    pub eid: Entity,
+    pub mode: BuyMode,
+}
+
+#[hyperfold_engine::system]
+fn buy_upgrade(
+    BuyUpgrade { eid, mode }: &BuyUpgrade,
+    upgrades: Vec<Upgrades>,
+    UpgradeBoxData { up_box, .. }: UpgradeBoxData,
+    events: &mut dyn Events,
+    research: &mut ResearchQueue,
+    dag: &mut NumDag,
+) {
+    if let Some(Upgrades { up, .. }) = upgrades.into_iter().find(|u| u.eid == eid) {
+        // One level of research at a time
+        if research.contains(&up.key) {
+            events.new_event(UpgradeDenied(*eid));
+            return;
+        }
+        match up.try_buy(&mut dag.0, *mode) {
+            Some((_, cost)) => {
+                if let Some(ms) = up.research_ms {
+                    research.push(&up.key, ms);
+                }
+                up_box.update = true;
+                events.new_event(UpgradeBought { eid: *eid, cost });
+            }
+            None => events.new_event(UpgradeDenied(*eid)),
+        }
+    }
+}
+
+// Purchase feedback
+#[hyperfold_engine::event]
+struct UpgradeBought {
+    pub eid: Entity,
+    pub cost: Number,
+}
+
+#[hyperfold_engine::event]
+struct UpgradeDenied(pub Entity);
+
+components!(
+    UpgradeFx,
+    up: &'a Upgrade,
+    pos: &'a Position,
+    opts: &'a RenderOpts,
+    pulse: &'a mut Pulse,
+    shake: &'a mut Shake
+);
+
+const FLASH_COLOR: hyperfold_engine::sdl2::SDL_Color = hyperfold_engine::sdl2::SDL_Color {
+    r: 255,
+    g: 255,
+    b: 255,
+    a: 160,
+};
+const DENIED_COLOR: hyperfold_engine::sdl2::SDL_Color = hyperfold_engine::sdl2::SDL_Color {
+    r: 255,
+    g: 0,
+    b: 0,
+    a: 160,
+};
+
 #[hyperfold_engine::system]
-fn drag_start_upgrade_box(
-    DragStart(id): &DragStart,
-    UpgradeBoxData { up_box, eid }: UpgradeBoxData,
+fn upgrade_bought_fx(
+    UpgradeBought { eid, cost }: &UpgradeBought,
+    icons: Vec<UpgradeFx>,
+    CrystalTextPos { pos: text_pos, .. }: CrystalTextPos,
+    entities: &mut dyn Components,
+    r: &Renderer,
 ) {
-    if id == eid {
-        up_box.v_scroll = 0.0;
+    let p = text_pos.0.center();
+    spawn_floating_text(
+        entities,
+        &format!("-{cost}"),
+        PointF {
+            x: p.x,
+            y: p.y - text_pos.0.h,
+        },
+        RED,
+        1000,
+    );
+
+    if let Some(UpgradeFx {
+        pos, opts, pulse, ..
+    }) = icons.into_iter().find(|i| i.eid == eid)
+    {
+        pulse.start(250);
+        if opts.visible {
+            spawn_flash(entities, r, pos.0.clone(), FLASH_COLOR, 250);
+        }
     }
 }
 
 #[hyperfold_engine::system]
-fn drag_upgrade_box(drag: &Drag, UpgradeBoxData { up_box, eid }: UpgradeBoxData) {
-    if &drag.eid == eid {
-        let prev = up_box.scroll;
-        up_box.scroll = (up_box.scroll - f32!(drag.mouse_dx))
-            .min(up_box.max_scroll)
-            .max(up_box.min_scroll);
-        up_box.v_scroll = -f32!(drag.mouse_dx) * 25.0;
-        if (prev - up_box.scroll).abs() >= 1.0 {
-            up_box.update = true;
+fn upgrade_denied_fx(
+    UpgradeDenied(eid): &UpgradeDenied,
+    icons: Vec<UpgradeFx>,
+    entities: &mut dyn Components,
+    r: &Renderer,
+) {
+    if let Some(UpgradeFx {
+        pos, opts, shake, ..
+    }) = icons.into_iter().find(|i| i.eid == eid)
+    {
+        shake.start(300);
+        if opts.visible {
+            spawn_flash(entities, r, pos.0.clone(), DENIED_COLOR, 300);
         }
     }
 }
 
+// Hit-testing
+#[hyperfold_engine::event]
+struct UpgradeHovered(pub Option<Entity>);
+
+#[hyperfold_engine::event]
+struct UpgradeClicked(pub Entity);
+
+#[hyperfold_engine::system]
+fn click_upgrade_box(
+    m: &Click,
+    upgrades: Vec<Upgrades>,
+    UpgradeBoxMouse {
+        up_box,
+        carousel,
+        pos,
+        eid,
+        ..
+    }: UpgradeBoxMouse,
+    events: &mut dyn Events,
+) {
+    // Further than this is a drag rather than a click
+    const CLICK_SLOP: f32 = 5.0;
+
+    let id = match up_box.open_id {
+        Some(id) if carousel.is_click(m, eid, CLICK_SLOP) => id,
+        _ => return,
+    };
+
+    let upgrades = filter_upgrades(upgrades, id);
+    let p = PointF {
+        x: m.mouse_x as f32,
+        y: m.mouse_y as f32,
+    };
+    if let Some((i, up)) = carousel
+        .hit_test(&pos.0, p)
+        .and_then(|i| upgrades.get(i).map(|up| (i, up)))
+    {
+        // Clicked upgrades are brought to the front
+        carousel.scroll_to(i);
+        events.new_event(UpgradeClicked(*up.eid));
+    }
+}
+
+// Keys and the mouse wheel move one upgrade at a time
 #[hyperfold_engine::system]
-fn update_upgrade_box(
-    Update(dt): &Update,
-    UpgradeBoxData { up_box, eid }: UpgradeBoxData,
-    drag_state: &DragState,
+fn upgrade_box_keys(
+    ActionPressed(action): &ActionPressed,
+    UpgradeBoxData { up_box, carousel }: UpgradeBoxData,
 ) {
-    if drag_state.dragging(*eid) {
+    if !up_box.is_open() {
         return;
     }
+    match action {
+        Action::PrevUpgrade => carousel.step(-1),
+        Action::NextUpgrade => carousel.step(1),
+        Action::CloseUpgrades => up_box.close(),
+        _ => (),
+    }
+}
 
-    let s = f32!(*dt) / 1000.0;
-    let prev = up_box.scroll;
-    up_box.scroll = (up_box.scroll + up_box.v_scroll * s)
-        .max(up_box.min_scroll)
-        .min(up_box.max_scroll);
-    if (prev - up_box.scroll).abs() >= 1.0 {
-        up_box.v_scroll *= 0.01_f32.powf(s);
-        up_box.update = true;
-    } else {
-        up_box.v_scroll = 0.0;
+#[hyperfold_engine::system]
+fn step_upgrade_box_wheel(
+    ev: &Scroll,
+    UpgradeBoxMouse {
+        up_box,
+        carousel,
+        pos,
+        opts,
+        ..
+    }: UpgradeBoxMouse,
+) {
+    let p = PointF {
+        x: ev.mouse_x as f32,
+        y: ev.mouse_y as f32,
+    };
+    let r = &pos.0;
+    let hovered = p.x >= r.x && p.x < r.x + r.w && p.y >= r.y && p.y < r.y + r.h;
+    if up_box.is_open() && opts.visible && hovered && ev.dy != 0 {
+        carousel.step(-ev.dy.signum());
+    }
+}
+
+// The front upgrade is the selected one
+#[hyperfold_engine::system]
+fn select_front_upgrade(
+    _: &Update,
+    upgrades: Vec<Upgrades>,
+    UpgradeBoxData { up_box, carousel }: UpgradeBoxData,
+) {
+    let id = match up_box.open_id {
+        Some(id) => id,
+        None => return,
+    };
+    let front = carousel
+        .front()
+        .and_then(|i| filter_upgrades(upgrades, id).get(i).map(|up| *up.eid));
+    if front.is_some() {
+        up_box.selected = front;
+    }
+}
+
+#[hyperfold_engine::system]
+fn track_mouse(m: &MouseMove, UpgradeBoxData { up_box, .. }: UpgradeBoxData) {
+    up_box.mouse = PointF {
+        x: m.mouse_x as f32,
+        y: m.mouse_y as f32,
+    };
+}
+
+#[hyperfold_engine::system]
+fn hover_upgrade_box(
+    _: &Update,
+    upgrades: Vec<Upgrades>,
+    UpgradeBoxMouse {
+        up_box,
+        carousel,
+        pos,
+        opts,
+        ..
+    }: UpgradeBoxMouse,
+    events: &mut dyn Events,
+) {
+    let hovered = match up_box.open_id {
+        Some(id) if opts.visible => {
+            let upgrades = filter_upgrades(upgrades, id);
+            carousel
+                .hit_test(&pos.0, up_box.mouse)
+                .and_then(|i| upgrades.get(i))
+                .map(|up| *up.eid)
+        }
+        _ => None,
+    };
+    if hovered != up_box.hovered {
+        up_box.hovered = hovered;
+        events.new_event(UpgradeHovered(hovered));
+    }
+}
+
+// Buy mode toggle
+#[hyperfold_engine::component(Singleton)]
+struct BuyModeButton;
+
+components!(
+    labels(BuyModeButton),
+    BuyModeButtonData,
+    pos: &'a mut Position,
+    opts: &'a mut RenderOpts,
+    text: &'a mut RenderComponent
+);
+
+#[hyperfold_engine::system]
+fn click_buy_mode(
+    m: &Click,
+    BuyModeButtonData { eid, text, .. }: BuyModeButtonData,
+    UpgradeBoxData { up_box, .. }: UpgradeBoxData,
+) {
+    if !m.is_me(eid) {
+        return;
+    }
+
+    up_box.buy_mode = up_box.buy_mode.next();
+    up_box.confirming = None;
+    text.try_as_mut(|t: &mut RenderText| t.set_text(up_box.buy_mode.label()));
+}
+
+#[hyperfold_engine::system]
+fn update_buy_mode(
+    _: &Update,
+    BuyModeButtonData { pos, opts, .. }: BuyModeButtonData,
+    UpgradeBoxPos {
+        up_box,
+        pos: box_pos,
+        ..
+    }: UpgradeBoxPos,
+) {
+    opts.set_visible(up_box.is_open());
+    pos.0 = Rect::from(
+        box_pos.0.x,
+        box_pos.0.y + box_pos.0.h,
+        60.0,
+        25.0,
+        Align::TopLeft,
+        Align::TopLeft,
+    );
+}
+
+#[hyperfold_engine::system]
+fn click_upgrade(
+    UpgradeClicked(eid): &UpgradeClicked,
+    UpgradeBoxData { up_box, .. }: UpgradeBoxData,
+    events: &mut dyn Events,
+) {
+    up_box.selected = Some(*eid);
+    // The first click only previews the total in the tooltip
+    if up_box.buy_mode != BuyMode::One && up_box.confirming != Some(*eid) {
+        up_box.confirming = Some(*eid);
+        return;
+    }
+    up_box.confirming = None;
+    events.new_event(BuyUpgrade {
+        eid: *eid,
+        mode: up_box.buy_mode,
+    });
+}
+
+#[hyperfold_engine::system]
+fn reset_upgrades(
+    _: &Prestige,
+    upgrades: Vec<Upgrades>,
+    UpgradeBoxData { up_box, .. }: UpgradeBoxData,
+    dag: &mut NumDag,
+) {
+    for Upgrades { up, .. } in upgrades {
+        dag.0.set(up.level, 0.into());
+        dag.0.set(up.spent, 0.into());
+    }
+    up_box.update = true;
+}
+
+// Unlocking
+#[hyperfold_engine::system]
+fn update_upgrade_states(
+    _: &Update,
+    upgrades: Vec<UpgradesMut>,
+    UpgradeBoxData { up_box, .. }: UpgradeBoxData,
+    dag: &mut NumDag,
+) {
+    const UNLOCK_ANIM_MS: u32 = 600;
+
+    for UpgradesMut { up, pulse, .. } in upgrades {
+        let state = up.compute_state(&mut dag.0);
+        if up.state != Some(state) {
+            // Don't animate upgrades that start unlocked
+            if up.state.is_some() && state == UpgradeState::Available {
+                pulse.start(UNLOCK_ANIM_MS);
+            }
+            up.state = Some(state);
+            up_box.update = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COSTS: [Cost; 7] = [
+        Cost::Geometric {
+            base: 10.0,
+            ratio: 1.0,
+        },
+        Cost::Geometric {
+            base: 10.0,
+            ratio: 1.15,
+        },
+        Cost::Geometric {
+            base: 10.0,
+            ratio: 0.5,
+        },
+        Cost::Polynomial {
+            base: 5.0,
+            power: 0,
+        },
+        Cost::Polynomial {
+            base: 5.0,
+            power: 1,
+        },
+        Cost::Polynomial {
+            base: 5.0,
+            power: 2,
+        },
+        Cost::Polynomial {
+            base: 5.0,
+            power: 3,
+        },
+    ];
+
+    fn summed(cost: Cost, level: u32, n: u32) -> f64 {
+        (level..level + n).map(|l| f64::from(cost.at(l))).sum()
+    }
+
+    #[test]
+    fn total_matches_summed_levels() {
+        for cost in COSTS {
+            for (level, n) in [(0, 0), (0, 1), (0, 10), (7, 25), (100, 100)] {
+                let (total, summed) = (f64::from(cost.total(level, n)), summed(cost, level, n));
+                assert!(
+                    (total - summed).abs() <= summed.abs() * 1e-9,
+                    "total({level}, {n}) = {total}, expected {summed}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn max_affordable_is_the_most_that_fits() {
+        const MAX: u32 = 1000;
+
+        for cost in COSTS {
+            for (level, magic) in [(0, 0.0), (0, 5.0), (0, 100.0), (5, 1e4), (20, 1e6)] {
+                let magic = Number::from(magic);
+                let n = cost.max_affordable(level, magic, MAX);
+                assert!(
+                    cost.total(level, n) <= magic,
+                    "max_affordable({level}) = {n}"
+                );
+                assert!(
+                    n == MAX || cost.total(level, n + 1) > magic,
+                    "max_affordable({level}) = {n}, but {} fits",
+                    n + 1
+                );
+            }
+        }
     }
 }
@@ -1,3 +1,6 @@
+// Icons in the upgrade carousel, more than this share the front elevation
+pub const UPGRADE_ICON_LAYERS: u8 = 32;
+
 #[repr(u8)]
 pub enum Elevations {
     Background = 0,
@@ -5,5 +8,14 @@ pub enum Elevations {
     Crystal,
     Wizards,
     Enemies,
+    HealthBars,
+    // Spell icons and the mana bar, then the cooldown sweeps and mana fill on top
+    SpellBar,
+    SpellBarFill,
     Upgrades,
+    // Carousel icons take one elevation each from here, back to front
+    UpgradeIcons,
+    UpgradePreview = Elevations::UpgradeIcons as u8 + UPGRADE_ICON_LAYERS,
+    UpgradeBadges,
+    Popups,
 }
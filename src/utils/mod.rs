@@ -1,2 +1,3 @@
 pub mod elevations;
 pub mod rand_sign;
+pub mod time;
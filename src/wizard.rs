@@ -1,14 +1,13 @@
+use std::f32::consts::TAU;
+
 use hyperfold_engine::{
-    add_components,
+    add_components, components,
     ecs::{
         entities::{Entity, NewEntity},
         events::core,
     },
     framework::{
-        event_system::{
-            components::DragTrigger,
-            events::{Click, Drag, Key},
-        },
+        event_system::{components::DragTrigger, events::Drag},
         physics,
         render_system::{
             self,
@@ -16,85 +15,192 @@ use hyperfold_engine::{
             AssetManager, Renderer,
         },
     },
-    sdl2::SDL_KeyCode::*,
     utils::{
         number::Number,
-        rect::{Align, Rect},
+        rect::{Align, PointF, Rect},
         timer::{Timer, TimerTrait},
     },
 };
 
 use crate::{
     _engine::{Components, Events},
-    crystal::{crystal_radius, CrystalNumbers, CrystalPos},
+    archetypes::{Attack, WizardPower, WizardType},
+    catalyst::CatalystNodes,
+    crystal::{crystal_radius, gain_magic, CrystalNumbers, CrystalPos},
+    enemies::enemy::Enemies,
     equation,
     fireball::CreateFireball,
+    health::HealthNodes,
+    input::{Action, ActionPressed, Input, Paused},
     param_dag::{Node, NodeDefault, NodeTrait, NumDag},
     parameters,
-    upgrades::{OpenUpgrades, Upgrade},
+    spells::SpellNumbers,
+    stats::{Stat, StatAdd, StatMult},
+    targeting::Targeting,
+    upgrades::{OpenUpgrades, UpgradeBoxData},
     utils::elevations::Elevations,
 };
 
-parameters!(WizardNumbers(Power));
+// Power: shared by every wizard type, see WizardPower, boosted by Magic Surge
+// Income: magic per second from every wizard, see init_archetype_numbers
+// FireRate: attack speed multiplier for every wizard, see HealthNodes::PlayerFireRate
+// FireballSpeed, MoveSpeed: pixels per second
+parameters!(WizardNumbers(
+    Power,
+    Income,
+    FireRate,
+    FireballSpeed,
+    MoveSpeed
+));
+
+const BASE_FIREBALL_SPEED: f64 = 150.0;
+const BASE_MOVE_SPEED: f64 = 250.0;
 
 #[hyperfold_engine::system(Init)]
 fn init_wizard_numbers(dag: &mut NumDag) {
     dag.0.add_node(
         WizardNumbers::Power,
-        equation!(|(m: CrystalNumbers::Magic,)| (*m + 1.into()).log10() + 1.into()),
+        equation!(
+            |(
+                m: CrystalNumbers::Magic,
+                c: CatalystNodes::Multiplier,
+                a: StatAdd(Stat::Power),
+                p: StatMult(Stat::Power),
+                s: SpellNumbers::SurgeMult,
+            )| ((*m + 1.into()).log10() + 1.into() + *a) * *p * *c * *s
+        ),
+    );
+    dag.0.add_node(
+        WizardNumbers::FireRate,
+        equation!(
+            |(a: StatAdd(Stat::FireRate), m: StatMult(Stat::FireRate),)| (*a + 1.into()) * *m
+        ),
+    );
+    dag.0.add_node(
+        WizardNumbers::FireballSpeed,
+        equation!(
+            |(a: StatAdd(Stat::FireballSpeed), m: StatMult(Stat::FireballSpeed),)| (*a
+                + BASE_FIREBALL_SPEED.into())
+                * *m
+        ),
+    );
+    dag.0.add_node(
+        WizardNumbers::MoveSpeed,
+        equation!(
+            |(a: StatAdd(Stat::MoveSpeed), m: StatMult(Stat::MoveSpeed),)| (*a
+                + BASE_MOVE_SPEED.into())
+                * *m
+        ),
     );
 }
 
+// The wizard controlled by the player
 #[hyperfold_engine::component(Singleton)]
 struct Wizard;
 
-#[hyperfold_engine::system(Init)]
-fn init_wizard(
+// Every wizard has a type, see Archetype
+#[hyperfold_engine::component]
+struct WizardKind(pub WizardType);
+
+// Wizards bought with magic, removed on prestige
+#[hyperfold_engine::component]
+struct Hired;
+
+// Spawns a wizard of the given type centered on pos
+pub fn spawn_wizard(
     entities: &mut dyn Components,
     r: &Renderer,
     am: &mut AssetManager,
-    camera: &render_system::Camera,
-) {
+    ty: WizardType,
+    pos: PointF,
+) -> Entity {
+    let arch = ty.archetype();
     let e = Entity::new();
-    let anim = Animation::new(5, 150);
+    let anim = Animation::new(arch.frames.0, arch.frames.1);
     let rc = render_system::RenderComponent::new(
-        RenderAsset::from_file("res/wizards/wizard_ss.png", r, am).with_animation(anim),
+        RenderAsset::from_file(arch.sprite, r, am).with_animation(anim),
     );
     add_components!(
         entities,
         e,
-        Wizard,
+        WizardKind(ty),
         render_system::RenderOpts::new(Elevations::Wizards as u8),
         rc,
         anim,
-        physics::Position(Rect {
-            x: camera.0.cx() - 50.0,
-            y: camera.0.cy() - 150.0,
-            w: 100.0,
-            h: 100.0,
-        }),
-        physics::PhysicsData::new(),
+        physics::Position(Rect::from(
+            pos.x,
+            pos.y,
+            arch.size,
+            arch.size,
+            Align::Center,
+            Align::Center
+        )),
         DragTrigger::OnMove,
-        Timer::new(1000),
+        Timer::new(arch.fire_rate_ms),
+        ty.owner(),
     );
+    e
 }
 
 #[hyperfold_engine::system(Init)]
-fn init_wizard_upgrades(entities: &mut dyn Components, events: &mut dyn Events) {
-    for i in 0..25 {
-        let e = Entity::new();
-        add_components!(entities, e, Upgrade::new(Wizard, i));
-    }
+fn init_wizard(
+    entities: &mut dyn Components,
+    events: &mut dyn Events,
+    r: &Renderer,
+    am: &mut AssetManager,
+    camera: &render_system::Camera,
+) {
+    let e = spawn_wizard(
+        entities,
+        r,
+        am,
+        WizardType::Fire,
+        PointF {
+            x: camera.0.cx(),
+            y: camera.0.cy() - 100.0,
+        },
+    );
+    add_components!(entities, e, Wizard, physics::PhysicsData::new());
+    events.new_event(OpenUpgrades::new(Wizard, e));
+}
 
-    events.new_event(OpenUpgrades::new(Wizard));
+// Sent when a wizard is hired or loaded from the save
+#[hyperfold_engine::event]
+struct SpawnWizard {
+    pub ty: WizardType,
+    pub pos: PointF,
+}
+
+#[hyperfold_engine::system]
+fn spawn_hired_wizard(
+    SpawnWizard { ty, pos }: &SpawnWizard,
+    entities: &mut dyn Components,
+    r: &Renderer,
+    am: &mut AssetManager,
+) {
+    let e = spawn_wizard(entities, r, am, *ty, *pos);
+    add_components!(entities, e, Hired);
 }
 
 hyperfold_engine::components!(labels(Wizard), WizardPos, pos: &'a physics::Position,);
 hyperfold_engine::components!(labels(Wizard), WizardPosMut, pos: &'a mut physics::Position,);
+components!(
+    labels(Hired),
+    HiredWizards,
+    kind: &'a WizardKind,
+    pos: &'a physics::Position
+);
+components!(
+    Wizards,
+    kind: &'a WizardKind,
+    pos: &'a mut physics::Position,
+    timer: &'a mut Timer
+);
 
+// Any wizard can be dragged into place
 #[hyperfold_engine::system]
-fn drag_wizard(drag: &Drag, WizardPosMut { pos, eid }: WizardPosMut) {
-    if &drag.eid == eid {
+fn drag_wizard(drag: &Drag, wizards: Vec<Wizards>) {
+    if let Some(Wizards { pos, .. }) = wizards.into_iter().find(|w| w.eid == &drag.eid) {
         pos.0.set_pos(
             drag.mouse_x as f32,
             drag.mouse_y as f32,
@@ -104,13 +210,6 @@ fn drag_wizard(drag: &Drag, WizardPosMut { pos, eid }: WizardPosMut) {
     }
 }
 
-#[hyperfold_engine::system]
-fn click_wizard(m: &Click, WizardPos { eid, .. }: WizardPos) {
-    if m.is_me(eid) {
-        eprintln!("Clicked :#)")
-    }
-}
-
 #[hyperfold_engine::system]
 fn track_wizard(
     update: &core::Update,
@@ -144,36 +243,118 @@ hyperfold_engine::components!(
     timer: &'a mut Timer,
 );
 
+// Direction comes from the keys held this frame
 #[hyperfold_engine::system]
-fn move_keys(ev: &Key, WizardData { pd, .. }: WizardData) {
-    const V: f32 = 250.0;
-    if let Some((val, amnt)) = match ev.0.key {
-        SDLK_a => Some((&mut pd.v.x, -V)),
-        SDLK_d => Some((&mut pd.v.x, V)),
-        SDLK_w => Some((&mut pd.v.y, -V)),
-        SDLK_s => Some((&mut pd.v.y, V)),
-        _ => None,
-    } {
-        if ev.0.down() {
-            *val += amnt;
-        }
-        if ev.0.up() {
-            *val -= amnt;
+fn apply_movement(
+    _: &core::Update,
+    WizardData { pd, .. }: WizardData,
+    input: &Input,
+    paused: &Paused,
+    dag: &mut NumDag,
+) {
+    let dir = match paused.0 {
+        true => PointF::new(),
+        false => PointF {
+            x: input.axis(Action::MoveLeft, Action::MoveRight),
+            y: input.axis(Action::MoveUp, Action::MoveDown),
+        },
+    };
+    let v = f64::from(*dag.0.get(WizardNumbers::MoveSpeed)) as f32;
+    pd.v = dir * v;
+}
+
+// Same as clicking the player's wizard
+#[hyperfold_engine::system]
+fn open_wizard_upgrades(
+    ActionPressed(action): &ActionPressed,
+    WizardPos { eid, .. }: WizardPos,
+    UpgradeBoxData { up_box, .. }: UpgradeBoxData,
+    events: &mut dyn Events,
+) {
+    if *action != Action::OpenUpgrades {
+        return;
+    }
+    match up_box.is_open() && up_box.owner() == Some(*eid) {
+        true => up_box.close(),
+        false => events.new_event(OpenUpgrades::new(Wizard, *eid)),
+    }
+}
+
+// Attack timers follow the fire rate, only the player's wizard is slowed by dying
+#[hyperfold_engine::system]
+fn update_fire_rates(
+    _: &core::Update,
+    wizards: Vec<Wizards>,
+    WizardPos { eid: player, .. }: WizardPos,
+    dag: &mut NumDag,
+) {
+    let rate = f64::from(*dag.0.get(WizardNumbers::FireRate));
+    let player_rate = f64::from(*dag.0.get(HealthNodes::PlayerFireRate));
+    for Wizards {
+        eid, kind, timer, ..
+    } in wizards
+    {
+        let rate = match eid == player {
+            true => player_rate,
+            false => rate,
+        };
+        let ms = (kind.0.archetype().fire_rate_ms as f64 / rate).max(1.0) as u32;
+        if timer.length != ms {
+            timer.length = ms;
         }
     }
 }
 
+// Wizards outside the crystal fire homing fireballs at enemies in range
 #[hyperfold_engine::system]
 fn update(
     dt: &core::Update,
     events: &mut dyn crate::_engine::Events,
-    WizardData { timer, pos, .. }: WizardData,
+    wizards: Vec<Wizards>,
+    enemies: Vec<Enemies>,
+    CrystalPos { pos: crys_pos, .. }: CrystalPos,
+    camera: &render_system::Camera,
+    targeting: &Targeting,
+    paused: &Paused,
     dag: &mut NumDag,
 ) {
-    for _ in 0..timer.add_time(dt.0) {
-        events.new_event(CreateFireball {
-            pos: pos.0.center(),
-            value: *dag.0.get(WizardNumbers::Power),
-        });
+    if paused.0 {
+        return;
+    }
+    let crys_rad = crystal_radius(camera);
+    for Wizards {
+        kind, pos, timer, ..
+    } in wizards
+    {
+        let value = *dag.0.get(WizardPower(kind.0));
+        let c = pos.0.center();
+        for _ in 0..timer.add_time(dt.0) {
+            let target = match c.dist(crys_pos.0.center()) <= crys_rad {
+                true => None,
+                false => targeting.pick(c, &enemies),
+            };
+            match kind.0.archetype().attack {
+                Attack::Fireball => events.new_event(CreateFireball {
+                    pos: c,
+                    value,
+                    target,
+                }),
+                Attack::Volley(n) => {
+                    let r = pos.0.w / 2.0;
+                    for i in 0..n {
+                        let a = TAU * i as f32 / n as f32;
+                        events.new_event(CreateFireball {
+                            pos: PointF {
+                                x: c.x + r * a.cos(),
+                                y: c.y + r * a.sin(),
+                            },
+                            value,
+                            target,
+                        });
+                    }
+                }
+                Attack::Channel => gain_magic(&mut dag.0, value),
+            }
+        }
     }
 }